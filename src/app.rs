@@ -1,23 +1,49 @@
+use chrono::Utc;
+use futures::StreamExt;
 use ratatui::{
     Frame, Terminal,
-    crossterm::event::{self, Event, KeyCode, KeyModifiers},
-    layout::{Constraint, Direction, Layout},
+    crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Direction, Flex, Layout},
     prelude::Backend,
     style::{Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Paragraph, Widget, Wrap},
+    widgets::{Block, ListState, Paragraph, Widget, Wrap},
+};
+use std::{
+    collections::HashMap,
+    io::{self},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::AbortHandle,
 };
-use reqwest::StatusCode;
-use std::io::{self};
 
 use crate::{
-    github::RepositoryClient,
+    batch::{self, Action, Change, Report},
+    config::{self, Config},
+    confirm::{Answer, Confirmation},
+    forge::{self, Affiliation, Forge, ForgeError, ForgeKind, Outcome, RepoQuery, TokenInfo},
+    gh,
+    oauth::{DeviceCode, DeviceFlow},
+    protected::protecting_pattern,
+    task::{self, Activity, TaskResult},
     ui::{
-        DARK_GRAY, GithubContent, LIGHT_RED, Status, draw_token_input, render_all_repositories,
-        render_selected_repositories,
+        DARK_GRAY, LIGHT_RED, Repository, RepositoryListing, Status, draw_confirmation_input,
+        draw_search_input, draw_token_input, render_all_repositories, render_batch_progress,
+        render_device_code, render_dry_run_banner, render_error, render_owners,
+        render_repository_details, render_rulesets, render_selected_repositories,
+        render_status_line, render_summary, spinner,
     },
 };
 
+// How often the spinner advances while an activity is running
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+// Classic tokens have 40 characters, fine-grained ones (`github_pat_...`) are more than twice as
+// long
+pub const MAX_TOKEN_LENGTH: usize = 255;
+
 pub struct App {
     // Running / Quit state
     pub state: RunningState,
@@ -27,36 +53,149 @@ pub struct App {
     pub token: String,
     // Current value of the input box
     pub token_input: String,
+    // Current value of the search box
+    pub search_input: String,
+    // Current answer to the confirmation challenge
+    pub confirm_input: String,
     // Are we waiting for the token
     pub waiting_for_token: bool,
     // Current mode of the app
     pub mode: Mode,
     // Are we waiting for repos
     pub waiting_for_repos: bool,
-    // Data that is being fetched from github
-    pub github_content: Option<GithubContent>,
+    // Data that is being fetched from the forge
+    pub listing: Option<RepositoryListing>,
     // Error state for the app
     pub error_state: Option<Error>,
-    // Client to get all repositories
-    pub repository_client: Option<RepositoryClient>,
+    // Client of the forge chosen at startup, GitHub or GitLab
+    pub repository_client: Option<Arc<dyn Forge>>,
+    // Settings from the config file
+    pub config: Config,
+    // Highlighted entry of the ruleset popup
+    pub ruleset_state: ListState,
+    // Feedback shown in place of the key hints until the next key press
+    pub status_message: Option<String>,
+    // Operation the user waits for, shown with a spinner and cancellable with 'Esc'
+    pub activity: Option<Activity>,
+    // Frame of the spinner shown while an activity runs
+    pub spinner_frame: usize,
+    // Background tasks report back through this channel
+    task_sender: UnboundedSender<TaskResult>,
+    task_receiver: UnboundedReceiver<TaskResult>,
+    // Task behind `activity`
+    activity_task: Option<AbortHandle>,
+    // Task fetching the next page of repositories
+    page_task: Option<AbortHandle>,
+    // Task fetching the details of the highlighted repository
+    details_task: Option<AbortHandle>,
+    // Owner and name of the repository behind `details_task`
+    details_repo: Option<(String, String)>,
+    // Why the details of a repository could not be loaded, shown in the details pane instead of
+    // a popup since nobody asked for them
+    details_error: Option<((String, String), String)>,
+    // Increased with every new listing, so pages of an outdated listing can be ignored
+    repos_generation: u64,
+    // Results of the last deletion batch, shown in the summary
+    pub reports: Vec<Report>,
+    // What has to be typed to run the batch, `None` if 'Enter' is enough
+    pub confirmation: Option<Confirmation>,
+    // The code to enter on GitHub while signing in through the device flow
+    pub device_code: Option<DeviceCode>,
+    // Login, scopes and expiry of the token, shown on the status line
+    pub token_info: Option<TokenInfo>,
+    // Accounts whose repositories can be listed: the user's own, then their organizations
+    pub owners: Vec<String>,
+    // Highlighted entry of the account picker
+    pub owner_state: ListState,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum Mode {
     Welcome,
     Auth,
+    Device,
+    Owners,
     Select,
+    Search,
+    Rules,
     Confirm,
+    Summary,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+// What went wrong, shown in a popup until it is dismissed
+#[derive(Debug)]
 pub enum Error {
-    DeleteRepository,
-    GetRepositoryOwner,
-    GetRepositories,
+    SignIn(ForgeError),
+    GetRepositories(ForgeError),
+    // The first repository of a batch that could not be changed, the summary lists all of them
+    Change(Report),
     NoRepositorySelected,
 }
 
+impl Error {
+    pub fn title(&self) -> String {
+        match self {
+            Error::SignIn(_) => String::from("Signing in failed"),
+            Error::GetRepositories(_) => String::from("Loading repositories failed"),
+            Error::Change(report) => format!(
+                "Could not {} {}/{}",
+                report.change.action.verb(),
+                report.change.owner,
+                report.change.name
+            ),
+            Error::NoRepositorySelected => String::from("Nothing selected"),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::SignIn(error) | Error::GetRepositories(error) => match error.status() {
+                Some(status) => format!("{error} ({})", status.as_u16()),
+                None => error.to_string(),
+            },
+            Error::Change(report) => report
+                .result
+                .message
+                .clone()
+                .unwrap_or_else(|| String::from(report.result.outcome.label())),
+            Error::NoRepositorySelected => String::from("No repository is marked yet."),
+        }
+    }
+
+    pub fn hint(&self, forge: ForgeKind) -> String {
+        match self {
+            Error::SignIn(error) | Error::GetRepositories(error) => error.hint(),
+            Error::Change(report) => match report.result.outcome {
+                Outcome::Forbidden => String::from(
+                    "Only admins of a repository can change it, check the token's scopes and your role.",
+                ),
+                Outcome::NotFound => String::from(
+                    "The repository may have been deleted, renamed or transferred in the meantime.",
+                ),
+                Outcome::RateLimited => {
+                    String::from("Wait a while, or lower `concurrency` in the config.")
+                }
+                Outcome::NetworkError => String::from("Check your network connection."),
+                Outcome::BackupFailed => format!(
+                    "Check the backup directory and that git can reach {}, nothing was deleted.",
+                    forge.name()
+                ),
+                _ => String::from("The summary lists every repository of the batch."),
+            },
+            Error::NoRepositorySelected => {
+                String::from("Mark repositories with 'Space', 'a' or 'p' before pressing 'Enter'.")
+            }
+        }
+    }
+
+    pub fn documentation_url(&self) -> Option<&str> {
+        match self {
+            Error::SignIn(error) | Error::GetRepositories(error) => error.documentation_url(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub enum RunningState {
     #[default]
@@ -72,27 +211,77 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
+        let (task_sender, task_receiver) = mpsc::unbounded_channel();
         Self {
             state: RunningState::Running,
             character_index: 0,
             token: String::new(),
             token_input: String::new(),
+            search_input: String::new(),
+            confirm_input: String::new(),
             waiting_for_token: false,
             mode: Mode::Welcome,
             waiting_for_repos: false,
-            github_content: None,
+            listing: None,
             error_state: None,
             repository_client: None,
+            config: Config::default(),
+            ruleset_state: ListState::default(),
+            status_message: None,
+            activity: None,
+            spinner_frame: 0,
+            task_sender,
+            task_receiver,
+            activity_task: None,
+            page_task: None,
+            details_task: None,
+            details_repo: None,
+            details_error: None,
+            repos_generation: 0,
+            reports: Vec::new(),
+            confirmation: None,
+            device_code: None,
+            token_info: None,
+            owners: Vec::new(),
+            owner_state: ListState::default(),
+        }
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            ..Self::new()
         }
     }
 
+    // Redraws whenever a key is pressed, a background task reports back or the spinner moves.
+    // Forge requests never run on this loop, so it stays responsive while they are in flight.
     pub async fn run(
         &mut self,
         mut terminal: Terminal<impl Backend>,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut events = EventStream::new();
+        let mut spinner_interval = tokio::time::interval(SPINNER_INTERVAL);
+
         while self.is_running() {
             self.draw(&mut terminal)?;
-            self.handle_events().await?;
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => self.handle_event(event?),
+                    None => self.exit(),
+                },
+                Some(result) = self.task_receiver.recv() => self.handle_task_result(result),
+                _ = spinner_interval.tick(), if self.activity.is_some() => {
+                    self.spinner_frame = self.spinner_frame.wrapping_add(1);
+                }
+            }
+        }
+
+        for task in [&self.activity_task, &self.page_task, &self.details_task]
+            .into_iter()
+            .flatten()
+        {
+            task.abort();
         }
         Ok(())
     }
@@ -104,134 +293,820 @@ impl App {
         Ok(())
     }
 
-    async fn handle_events(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Event::Key(key_event) = event::read()? {
-            if key_event.modifiers.contains(KeyModifiers::CONTROL)
-                && key_event.code == KeyCode::Char('c')
-            {
-                self.exit();
+    fn handle_event(&mut self, event: Event) {
+        // Some terminals also report key releases
+        if let Event::Key(key_event) = event
+            && key_event.kind == KeyEventKind::Press
+        {
+            self.handle_key(key_event);
+        }
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent) {
+        if key_event.modifiers.contains(KeyModifiers::CONTROL)
+            && key_event.code == KeyCode::Char('c')
+        {
+            self.exit();
+            return;
+        }
+        self.status_message = None;
+
+        // While an activity runs the only thing to do is to wait or to cancel it
+        if self.activity.is_some() {
+            if key_event.code == KeyCode::Esc {
+                self.cancel_activity();
             }
+            return;
+        }
 
-            match self.mode {
-                Mode::Welcome => match key_event.code {
-                    KeyCode::Enter => {
-                        const PATH: &str = "https://github.com/settings/tokens/new?scopes=delete_repo,repo&description=Repo%20Remover%20Token";
-                        self.waiting_for_token = true;
-                        self.mode = Mode::Auth;
+        // The error popup has to be dismissed first
+        if self.error_state.is_some() {
+            if matches!(key_event.code, KeyCode::Enter | KeyCode::Esc) {
+                self.error_state = None;
+            }
+            return;
+        }
 
-                        if let Err(e) = open::that(PATH) {
-                            eprintln!("Failed to open browser: {e}");
-                        }
+        match self.mode {
+            Mode::Welcome => match key_event.code {
+                KeyCode::Enter => {
+                    self.waiting_for_token = true;
+                    self.mode = Mode::Auth;
+
+                    let url = self.config.host().new_token_url();
+                    if let Err(e) = open::that(&url) {
+                        self.status_message =
+                            Some(format!("Could not open a browser ({e}), go to {url}"));
                     }
-                    KeyCode::Char('q') | KeyCode::Esc => self.exit(),
-                    _ => {}
-                },
-                Mode::Auth => match key_event.code {
-                    KeyCode::Char(to_insert) => {
-                        self.enter_char(to_insert);
+                }
+                KeyCode::Char('g') => self.sign_in_with_gh(),
+                KeyCode::Char('d') => self.start_device_flow(),
+                KeyCode::Char('q') | KeyCode::Esc => self.exit(),
+                _ => {}
+            },
+            // Only reached while waiting, and then only 'Esc' does something
+            Mode::Device => {}
+            Mode::Auth => match key_event.code {
+                KeyCode::Char(to_insert) => {
+                    self.enter_char(to_insert);
+                }
+                KeyCode::Enter => {
+                    let token = self.token_input.trim().to_owned();
+                    self.submit_message();
+                    self.sign_in(token);
+                }
+                KeyCode::Backspace => self.delete_char(),
+                KeyCode::Left => self.move_cursor_left(),
+                KeyCode::Right => self.move_cursor_right(),
+                KeyCode::Esc => {
+                    self.mode = Mode::Welcome;
+                    self.reset_cursor();
+                    self.token = String::new();
+                    self.token_input = String::new();
+                }
+                _ => {}
+            },
+            Mode::Owners => match key_event.code {
+                KeyCode::Down | KeyCode::Char('j') => self.owner_state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.owner_state.select_previous(),
+                KeyCode::Enter => {
+                    if let Some(index) = self.owner_state.selected() {
+                        self.load_owner(index);
+                    }
+                }
+                KeyCode::Esc if self.listing.is_some() => self.mode = Mode::Select,
+                KeyCode::Char('q') | KeyCode::Esc => self.exit(),
+                _ => {}
+            },
+            Mode::Select => match key_event.code {
+                KeyCode::Esc if !self.search_input.is_empty() => self.clear_search(),
+                KeyCode::Char('q') | KeyCode::Esc => self.exit(),
+                KeyCode::Char('r') => self.open_rulesets(),
+                KeyCode::Char('o') => self.pick_owner(),
+                KeyCode::Char('/') => {
+                    self.mode = Mode::Search;
+                    self.character_index = self.search_input.chars().count();
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.select_next();
+                    self.load_highlighted_details();
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.select_previous();
+                    self.load_highlighted_details();
+                }
+                KeyCode::Char(' ') => {
+                    self.toggle_status(Action::Delete);
+                }
+                KeyCode::Char('a') => self.toggle_status(Action::Archive),
+                KeyCode::Char('p') => self.toggle_status(Action::MakePrivate),
+                KeyCode::Char('1') => self.toggle_affiliation(Affiliation::Owner),
+                KeyCode::Char('2') => self.toggle_affiliation(Affiliation::Collaborator),
+                KeyCode::Char('3') => self.toggle_affiliation(Affiliation::OrganizationMember),
+                KeyCode::Char('v') => self.cycle_visibility(),
+                KeyCode::Char('s') => {
+                    if let Some(listing) = self.listing.as_mut() {
+                        listing.cycle_sort_key();
+                    }
+                    self.load_highlighted_details();
+                }
+                KeyCode::Char('S') => {
+                    if let Some(listing) = self.listing.as_mut() {
+                        listing.toggle_sort_direction();
                     }
-                    KeyCode::Enter => {
-                        self.token = self.token_input.clone();
-                        self.submit_message();
-                        self.waiting_for_token = false;
-                        self.waiting_for_repos = true;
-                        let repository_client = RepositoryClient::new(&self.token);
-                        self.repository_client = Some(repository_client);
-                        if let Some(repository_client) = self.repository_client.as_mut() {
-                            match repository_client.get_owner().await {
-                                Ok(owner) => match repository_client.get_repos(&owner).await {
-                                    Ok(github_content) => {
-                                        self.github_content = Some(github_content);
-                                        self.waiting_for_repos = false;
-                                        self.mode = Mode::Select;
-                                    }
-                                    Err(_) => self.error_state = Some(Error::GetRepositories),
-                                },
-                                Err(_) => {
-                                    self.error_state = Some(Error::GetRepositoryOwner);
-                                }
-                            }
+                    self.load_highlighted_details();
+                }
+                KeyCode::Enter => {
+                    if let Some(listing) = &self.listing {
+                        let at_least_one_selected = listing
+                            .repos
+                            .iter()
+                            .any(|repo| repo.status != Status::Unselected);
+
+                        if at_least_one_selected {
+                            self.start_confirmation();
+                        } else {
+                            self.error_state = Some(Error::NoRepositorySelected);
                         }
                     }
-                    KeyCode::Backspace => self.delete_char(),
-                    KeyCode::Left => self.move_cursor_left(),
-                    KeyCode::Right => self.move_cursor_right(),
-                    KeyCode::Esc => {
-                        self.mode = Mode::Welcome;
-                        self.reset_cursor();
-                        self.token = String::new();
-                        self.token_input = String::new();
+                }
+                _ => {}
+            },
+            Mode::Search => match key_event.code {
+                KeyCode::Char(to_insert) => {
+                    self.enter_char(to_insert);
+                    self.apply_search();
+                }
+                KeyCode::Backspace => {
+                    self.delete_char();
+                    self.apply_search();
+                }
+                KeyCode::Left => self.move_cursor_left(),
+                KeyCode::Right => self.move_cursor_right(),
+                KeyCode::Down => {
+                    self.select_next();
+                    self.load_highlighted_details();
+                }
+                KeyCode::Up => {
+                    self.select_previous();
+                    self.load_highlighted_details();
+                }
+                // Keep the filter and go back to marking repositories
+                KeyCode::Enter => self.mode = Mode::Select,
+                KeyCode::Esc => {
+                    self.clear_search();
+                    self.mode = Mode::Select;
+                }
+                _ => {}
+            },
+            Mode::Rules => match key_event.code {
+                KeyCode::Down | KeyCode::Char('j') => self.ruleset_state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.ruleset_state.select_previous(),
+                KeyCode::Enter => {
+                    self.mode = Mode::Select;
+                    if let Some(index) = self.ruleset_state.selected() {
+                        self.apply_ruleset(index);
                     }
-                    _ => {}
-                },
-                Mode::Select => match key_event.code {
-                    KeyCode::Char('q') | KeyCode::Esc => self.exit(),
-                    KeyCode::Down | KeyCode::Char('j') => self.select_next(),
-                    KeyCode::Up | KeyCode::Char('k') => self.select_previous(),
-                    KeyCode::Char(' ') => {
-                        self.toggle_status();
+                }
+                KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Select,
+                _ => {}
+            },
+            Mode::Confirm => match key_event.code {
+                KeyCode::Char(to_insert) => self.enter_char(to_insert),
+                KeyCode::Backspace => self.delete_char(),
+                KeyCode::Left => self.move_cursor_left(),
+                KeyCode::Right => self.move_cursor_right(),
+                KeyCode::Enter => self.submit_confirmation(),
+                KeyCode::Esc => {
+                    self.mode = Mode::Select;
+                    self.confirmation = None;
+                }
+                _ => {}
+            },
+            Mode::Summary => match key_event.code {
+                KeyCode::Enter | KeyCode::Esc => self.mode = Mode::Select,
+                KeyCode::Char('q') => self.exit(),
+                _ => {}
+            },
+        }
+    }
+
+    fn handle_task_result(&mut self, result: TaskResult) {
+        match result {
+            TaskResult::TokenChecked(result) => {
+                self.finish_activity();
+                match result {
+                    Ok(token_info) => self.load_owners(token_info),
+                    Err(error) => {
+                        self.waiting_for_repos = false;
+                        self.error_state = Some(Error::SignIn(error));
+                        self.ask_for_token();
+                    }
+                }
+            }
+            TaskResult::Organizations(result) => {
+                self.finish_activity();
+                let Some(token_info) = &self.token_info else {
+                    return;
+                };
+                self.owners = vec![token_info.login.clone()];
+                match result {
+                    Ok(organizations) if !organizations.is_empty() => {
+                        self.owners.extend(organizations);
+                        self.owner_state.select(Some(0));
+                        self.waiting_for_repos = false;
+                        self.mode = Mode::Owners;
                     }
-                    KeyCode::Enter => {
-                        if let Some(github_content) = &self.github_content {
-                            let at_least_one_selected = github_content
-                                .repos
-                                .iter()
-                                .any(|repo| repo.status == Status::Selected);
-
-                            if at_least_one_selected {
-                                self.mode = Mode::Confirm
-                            } else {
-                                self.error_state = Some(Error::NoRepositorySelected);
-                            }
+                    Ok(_) => self.load_owner(0),
+                    // The user's own repositories are still worth listing
+                    Err(error) => {
+                        self.status_message =
+                            Some(format!("Could not load your organizations: {error}"));
+                        self.load_owner(0);
+                    }
+                }
+            }
+            TaskResult::Repositories(result) => {
+                self.finish_activity();
+                self.waiting_for_repos = false;
+                match result {
+                    Ok(mut listing) => {
+                        // Keep the way the previous listing was looked at
+                        if let Some(previous) = &self.listing {
+                            listing.sort_key = previous.sort_key;
+                            listing.sort_ascending = previous.sort_ascending;
                         }
+                        listing.set_search(&self.search_input);
+                        self.listing = Some(listing);
+                        self.mode = Mode::Select;
+                        self.repos_generation += 1;
+                        self.load_next_page();
+                        self.load_highlighted_details();
                     }
-                    _ => {}
-                },
-                Mode::Confirm => match key_event.code {
-                    KeyCode::Enter => {
-                        if let Some(repositories) = &mut self.github_content {
-                            let selected_repos: Vec<String> = repositories
-                                .repos
-                                .iter()
-                                .filter(|r| r.status == Status::Selected)
-                                .map(|r| r.name.clone())
-                                .collect();
-
-                            for repo_name in &selected_repos {
-                                let status_code = self
-                                    .repository_client
-                                    .as_mut()
-                                    .unwrap()
-                                    .delete_repo(&repositories.owner, repo_name)
-                                    .await?;
-
-                                if status_code.is_client_error() {
-                                    self.error_state = Some(Error::DeleteRepository);
-                                }
-
-                                if status_code == StatusCode::NO_CONTENT {
-                                    // Update repository list and remove the ones we just deleted
-                                    repositories.repos = repositories
-                                        .repos
-                                        .iter()
-                                        .filter(|repo| &repo.name != repo_name)
-                                        .cloned()
-                                        .collect();
-                                }
-                            }
-
-                            // Once deleted we go back to Select Mode
-                            self.mode = Mode::Select;
+                    Err(error) => {
+                        self.error_state = Some(error);
+                        self.ask_for_token();
+                    }
+                }
+            }
+            TaskResult::Page { generation, result } => {
+                if generation != self.repos_generation {
+                    return;
+                }
+                self.page_task = None;
+                if let Some(listing) = self.listing.as_mut() {
+                    match result {
+                        Ok((repos, next_page)) => {
+                            listing.repos.extend(repos);
+                            listing.next_page = next_page;
+                            listing.refresh_view();
+                            self.load_next_page();
+                            self.load_highlighted_details();
+                        }
+                        Err(error) => {
+                            // Pagination stops here, the repositories we already have stay usable
+                            listing.next_page = None;
+                            self.error_state = Some(Error::GetRepositories(error));
                         }
                     }
-                    KeyCode::Esc => {
-                        self.mode = Mode::Select;
+                }
+            }
+            TaskResult::Details {
+                owner,
+                name,
+                result,
+            } => {
+                // The answer for a repository that is no longer highlighted may still arrive
+                let repo = (owner, name);
+                if self.details_repo.as_ref() != Some(&repo) {
+                    return;
+                }
+                self.details_task = None;
+                self.details_repo = None;
+                self.details_error = result.err().map(|error| (repo, error.to_string()));
+            }
+            TaskResult::UpstreamCompared {
+                ruleset,
+                commits_ahead,
+            } => {
+                self.finish_activity();
+                self.select_matching(ruleset, &commits_ahead);
+            }
+            TaskResult::Changed(report) => {
+                if let Some(Activity::Changing { done, .. }) = self.activity.as_mut() {
+                    *done += 1;
+                }
+                match report.result.outcome {
+                    Outcome::Succeeded => self.apply_change(&report.change),
+                    // The listing stays as it is, the summary tells what would have changed
+                    Outcome::DryRun => {}
+                    // Only the first failure pops up, the summary has the others
+                    _ => {
+                        if self.error_state.is_none() {
+                            self.error_state = Some(Error::Change(report.clone()));
+                        }
                     }
-                    _ => {}
-                },
+                }
+                self.reports.push(report);
+            }
+            TaskResult::BatchFinished => {
+                self.finish_activity();
+                self.mode = Mode::Summary;
+            }
+            TaskResult::DeviceCode(result) => {
+                self.finish_activity();
+                match result {
+                    Ok(device_code) => self.wait_for_authorization(device_code),
+                    Err(e) => self.status_message = Some(e.to_string()),
+                }
+            }
+            TaskResult::DeviceToken(result) => {
+                self.finish_activity();
+                self.device_code = None;
+                match result {
+                    Ok(token) => self.sign_in(token),
+                    Err(e) => {
+                        self.mode = Mode::Welcome;
+                        self.status_message = Some(e.to_string());
+                    }
+                }
             }
         }
-        Ok(())
+    }
+
+    // Loads the repositories of the token's owner, either after entering the token in Auth mode or
+    // right at the start with a token from the environment or a file
+    pub fn sign_in(&mut self, token: String) {
+        self.token = token;
+        self.waiting_for_token = false;
+        self.waiting_for_repos = true;
+        let repository_client =
+            forge::connect(&self.config.host(), &self.token, self.config.dry_run);
+        self.repository_client = Some(repository_client.clone());
+        self.start_activity(Activity::CheckingToken, async move {
+            TaskResult::TokenChecked(repository_client.check_token().await)
+        });
+    }
+
+    // Organizations are asked for first, the user then picks whose repositories to list
+    fn load_owners(&mut self, token_info: TokenInfo) {
+        let missing_scopes = token_info.missing_scopes();
+        if !missing_scopes.is_empty() {
+            self.status_message = Some(format!(
+                "The token lacks the scopes {}, some changes will fail",
+                missing_scopes.join(", ")
+            ));
+        }
+        self.token_info = Some(token_info);
+        let Some(repository_client) = self.repository_client.clone() else {
+            return;
+        };
+        self.start_activity(Activity::LoadingOrganizations, async move {
+            TaskResult::Organizations(repository_client.get_organizations().await)
+        });
+    }
+
+    // Lists the repositories of the account at the index of `owners`, the user's own or one of
+    // their organizations. Selections of the previous account are dropped.
+    fn load_owner(&mut self, index: usize) {
+        let (Some(repository_client), Some(owner)) = (
+            self.repository_client.clone(),
+            self.owners.get(index).cloned(),
+        ) else {
+            return;
+        };
+        if let Some(page_task) = self.page_task.take() {
+            page_task.abort();
+        }
+        // The visibility and affiliations picked so far carry over to the other account
+        let mut query = self
+            .listing
+            .as_ref()
+            .map(|listing| listing.query.clone())
+            .unwrap_or_default();
+        query.organization = (index > 0).then(|| owner.clone());
+        self.waiting_for_repos = true;
+        self.start_activity(Activity::LoadingRepositories, async move {
+            let result = repository_client
+                .get_repos(&owner, &query)
+                .await
+                .map_err(Error::GetRepositories);
+            TaskResult::Repositories(result)
+        });
+    }
+
+    fn pick_owner(&mut self) {
+        if self.owners.len() > 1 {
+            self.mode = Mode::Owners;
+        } else {
+            self.status_message = Some(String::from("You are not a member of any organization"));
+        }
+    }
+
+    // A token from the environment, gh or the device flow skipped Auth, it's needed now to enter
+    // another one
+    fn ask_for_token(&mut self) {
+        if matches!(self.mode, Mode::Welcome | Mode::Device) {
+            self.waiting_for_token = true;
+            self.mode = Mode::Auth;
+        }
+    }
+
+    // Reuses the token of `gh auth login`, so there is no need to create one
+    fn sign_in_with_gh(&mut self) {
+        if self.config.forge != ForgeKind::Github {
+            self.status_message = Some(String::from("The gh login only works with GitHub"));
+            return;
+        }
+        let host = self.config.host();
+        match gh::load(&host.name) {
+            Ok(Some(login)) => self.sign_in(login.token),
+            Ok(None) => {
+                self.status_message = Some(format!(
+                    "gh is not logged in to {}, run 'gh auth login' first",
+                    host.name
+                ));
+            }
+            Err(e) => self.status_message = Some(e.to_string()),
+        }
+    }
+
+    // Signs in through the OAuth device flow, the user enters a code on GitHub instead of
+    // creating and pasting a token
+    fn start_device_flow(&mut self) {
+        if self.config.forge != ForgeKind::Github {
+            self.status_message = Some(String::from(
+                "Signing in with a code only works with GitHub",
+            ));
+            return;
+        }
+        let Some(client_id) = &self.config.oauth_client_id else {
+            let location = config::config_path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| String::from("the config file"));
+            self.status_message = Some(format!(
+                "Set oauth_client_id in {location} to sign in with a code"
+            ));
+            return;
+        };
+        let device_flow = DeviceFlow::new(client_id, &self.config.host());
+        self.start_activity(Activity::RequestingDeviceCode, async move {
+            TaskResult::DeviceCode(device_flow.request_code().await)
+        });
+    }
+
+    // Signing in with a code needs the client ID of an OAuth app, which only GitHub offers here
+    fn device_flow_available(&self) -> bool {
+        self.config.forge == ForgeKind::Github && self.config.oauth_client_id.is_some()
+    }
+
+    fn wait_for_authorization(&mut self, device_code: DeviceCode) {
+        let Some(client_id) = &self.config.oauth_client_id else {
+            return;
+        };
+        // The terminal belongs to the TUI, so failures go to the footer instead of stderr
+        if let Err(e) = open::that(&device_code.verification_uri) {
+            self.status_message = Some(format!(
+                "Could not open a browser ({e}), go to {}",
+                device_code.verification_uri
+            ));
+        }
+        let device_flow = DeviceFlow::new(client_id, &self.config.host());
+        let code = device_code.clone();
+        self.device_code = Some(device_code);
+        self.mode = Mode::Device;
+        self.start_activity(Activity::WaitingForAuthorization, async move {
+            TaskResult::DeviceToken(device_flow.poll_token(&code).await)
+        });
+    }
+
+    fn start_activity<F>(&mut self, activity: Activity, future: F)
+    where
+        F: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.activity = Some(activity);
+        self.activity_task = Some(task::spawn(&self.task_sender, future));
+    }
+
+    fn finish_activity(&mut self) {
+        self.activity = None;
+        self.activity_task = None;
+    }
+
+    fn cancel_activity(&mut self) {
+        if let Some(activity_task) = self.activity_task.take() {
+            activity_task.abort();
+        }
+        match self.activity.take() {
+            Some(
+                Activity::CheckingToken
+                | Activity::LoadingOrganizations
+                | Activity::LoadingRepositories,
+            ) => self.waiting_for_repos = false,
+            Some(Activity::WaitingForAuthorization) => {
+                self.device_code = None;
+                self.mode = Mode::Welcome;
+            }
+            Some(Activity::Changing { done, total }) => {
+                self.mode = Mode::Summary;
+                self.status_message = Some(format!(
+                    "Batch cancelled after {done} of {total} repositories"
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    // Fetches the next page of the current listing in the background, if there is one
+    fn load_next_page(&mut self) {
+        if let (Some(repository_client), Some(listing)) = (&self.repository_client, &self.listing)
+            && let Some(url) = listing.next_page.clone()
+        {
+            let repository_client = repository_client.clone();
+            let generation = self.repos_generation;
+            self.page_task = Some(task::spawn(&self.task_sender, async move {
+                let result = repository_client.get_repos_page(&url).await;
+                TaskResult::Page { generation, result }
+            }));
+        }
+    }
+
+    fn toggle_affiliation(&mut self, affiliation: Affiliation) {
+        if let Some(listing) = &self.listing {
+            let mut query = listing.query.clone();
+            if query.organization.is_some() {
+                self.status_message = Some(String::from(
+                    "Organization repositories can only be filtered by visibility",
+                ));
+                return;
+            }
+            if query.toggle_affiliation(affiliation) {
+                self.reload_repos(query);
+            }
+        }
+    }
+
+    fn cycle_visibility(&mut self) {
+        if let Some(listing) = &self.listing {
+            let mut query = listing.query.clone();
+            query.visibility = query.visibility.next();
+            self.reload_repos(query);
+        }
+    }
+
+    // Fetches the list again for a different filter. Selections are dropped, so nothing that is
+    // no longer visible can end up being deleted.
+    fn reload_repos(&mut self, query: RepoQuery) {
+        if let (Some(repository_client), Some(listing)) = (&self.repository_client, &self.listing) {
+            if let Some(page_task) = self.page_task.take() {
+                page_task.abort();
+            }
+            let repository_client = repository_client.clone();
+            let owner = listing.owner.clone();
+            self.start_activity(Activity::LoadingRepositories, async move {
+                let result = repository_client
+                    .get_repos(&owner, &query)
+                    .await
+                    .map_err(Error::GetRepositories);
+                TaskResult::Repositories(result)
+            });
+        }
+    }
+
+    // Starts fetching the details of the highlighted repository unless they are cached or
+    // already on their way. Called whenever the highlighted repository may have changed, a
+    // request for the previously highlighted one is no longer needed.
+    fn load_highlighted_details(&mut self) {
+        let highlighted = self
+            .listing
+            .as_ref()
+            .and_then(|listing| listing.highlighted())
+            .map(|repo| (repo.owner.clone(), repo.name.clone()));
+        if self.details_task.is_some() && self.details_repo == highlighted {
+            return;
+        }
+        if let Some(details_task) = self.details_task.take() {
+            details_task.abort();
+        }
+        self.details_repo = None;
+        if let Some(repository_client) = &self.repository_client
+            && let Some((owner, name)) = highlighted
+            && repository_client
+                .cached_repo_details(&owner, &name)
+                .is_none()
+        {
+            let repository_client = repository_client.clone();
+            self.details_repo = Some((owner.clone(), name.clone()));
+            self.details_task = Some(task::spawn(&self.task_sender, async move {
+                let result = repository_client.get_repo_details(&owner, &name).await;
+                TaskResult::Details {
+                    owner,
+                    name,
+                    result,
+                }
+            }));
+        }
+    }
+
+    fn open_rulesets(&mut self) {
+        if self.config.rulesets.is_empty() {
+            let location = config::config_path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| String::from("the config file"));
+            self.status_message = Some(format!("No rulesets configured, add some to {location}"));
+        } else {
+            self.ruleset_state.select(Some(0));
+            self.mode = Mode::Rules;
+        }
+    }
+
+    // Rulesets comparing forks with their upstream need requests first, all others are applied
+    // right away
+    fn apply_ruleset(&mut self, index: usize) {
+        let Some(ruleset) = self.config.rulesets.get(index) else {
+            return;
+        };
+        if !ruleset.needs_upstream_comparison() {
+            self.select_matching(index, &HashMap::new());
+            return;
+        }
+        let (Some(repository_client), Some(listing)) = (&self.repository_client, &self.listing)
+        else {
+            return;
+        };
+
+        let forks: Vec<(String, String, String)> = listing
+            .visible
+            .iter()
+            .map(|visible| &listing.repos[visible.index])
+            .filter(|repo| repo.metadata.fork && repo.status == Status::Unselected)
+            .map(|repo| {
+                let branch = repo.metadata.default_branch.clone();
+                (repo.owner.clone(), repo.name.clone(), branch)
+            })
+            .collect();
+        let repository_client = repository_client.clone();
+        self.start_activity(Activity::ApplyingRuleset, async move {
+            let mut commits_ahead = HashMap::new();
+            for (owner, name, branch) in forks {
+                // Forks that can't be compared simply don't match
+                if let Ok(Some(ahead)) = repository_client
+                    .commits_ahead_of_parent(&owner, &name, &branch)
+                    .await
+                {
+                    commits_ahead.insert(format!("{owner}/{name}"), ahead);
+                }
+            }
+            TaskResult::UpstreamCompared {
+                ruleset: index,
+                commits_ahead,
+            }
+        });
+    }
+
+    // Marks every visible repository that matches a rule of the ruleset for deletion. Repositories
+    // that are already marked keep their status, nothing gets unselected.
+    fn select_matching(&mut self, index: usize, commits_ahead: &HashMap<String, u32>) {
+        let (Some(ruleset), Some(listing)) =
+            (self.config.rulesets.get(index), self.listing.as_mut())
+        else {
+            return;
+        };
+
+        let now = Utc::now();
+        let candidates: Vec<usize> = listing
+            .visible
+            .iter()
+            .map(|visible| visible.index)
+            .collect();
+        let mut matched = 0;
+        for i in candidates {
+            let repo = &mut listing.repos[i];
+            let protected = protecting_pattern(&self.config.protected, &repo.owner, &repo.name);
+            if repo.status != Status::Unselected
+                || protected.is_some()
+                || !repo.metadata.can_administer()
+            {
+                continue;
+            }
+            let ahead = commits_ahead
+                .get(&format!("{}/{}", repo.owner, repo.name))
+                .copied();
+            if let Some(rule) = ruleset.first_match(repo, ahead, now) {
+                repo.matched_rule = Some(rule.to_owned());
+                repo.status = Status::Marked(Action::Delete);
+                matched += 1;
+            }
+        }
+
+        self.status_message = Some(format!(
+            "Ruleset '{}' selected {matched} repositories",
+            ruleset.name
+        ));
+    }
+
+    // Deletions have to be confirmed by typing, see `Confirmation`. Batches without deletions only
+    // need 'Enter'.
+    fn start_confirmation(&mut self) {
+        let Some(listing) = &self.listing else {
+            return;
+        };
+        let deleted: Vec<&Repository> = listing
+            .repos
+            .iter()
+            .filter(|repo| repo.status == Status::Marked(Action::Delete))
+            .collect();
+        self.confirmation = (!deleted.is_empty()).then(|| {
+            Confirmation::new(
+                &listing.owner,
+                &deleted,
+                self.config.confirm_names_above,
+                Utc::now(),
+            )
+        });
+        self.confirm_input.clear();
+        self.reset_cursor();
+        self.mode = Mode::Confirm;
+    }
+
+    fn submit_confirmation(&mut self) {
+        let answer = match self.confirmation.as_mut() {
+            Some(confirmation) => confirmation.answer(&self.confirm_input),
+            None => Answer::Confirmed,
+        };
+        self.confirm_input.clear();
+        self.reset_cursor();
+        match answer {
+            Answer::Wrong => {
+                self.status_message = Some(String::from("That doesn't match, try again"));
+            }
+            Answer::Next => {}
+            Answer::Confirmed => {
+                self.confirmation = None;
+                self.apply_marked();
+            }
+        }
+    }
+
+    // Applies the marked actions in one batch in the background. Each result is reported on its
+    // own, so the list updates and the progress gauge fills while the batch is running.
+    fn apply_marked(&mut self) {
+        let (Some(repository_client), Some(listing)) = (&self.repository_client, &self.listing)
+        else {
+            return;
+        };
+
+        let changes: Vec<Change> = listing
+            .repos
+            .iter()
+            .filter_map(|r| match r.status {
+                Status::Marked(action) => Some(Change {
+                    action,
+                    owner: r.owner.clone(),
+                    name: r.name.clone(),
+                    clone_url: r.metadata.clone_url.clone(),
+                }),
+                Status::Unselected => None,
+            })
+            .collect();
+        let repository_client = repository_client.clone();
+        let sender = self.task_sender.clone();
+        let options = batch::Options {
+            concurrency: self.config.concurrency,
+            backup: self.config.backup.clone(),
+            protected: self.config.protected.clone(),
+        };
+        let activity = Activity::Changing {
+            done: 0,
+            total: changes.len(),
+        };
+        self.reports.clear();
+        self.start_activity(activity, async move {
+            batch::run(repository_client, changes, options, sender).await;
+            TaskResult::BatchFinished
+        });
+    }
+
+    // Updates the listing after a change went through
+    fn apply_change(&mut self, change: &Change) {
+        let Some(listing) = self.listing.as_mut() else {
+            return;
+        };
+        let is_changed = |repo: &Repository| repo.owner == change.owner && repo.name == change.name;
+        if change.action == Action::Delete {
+            listing.repos.retain(|repo| !is_changed(repo));
+        } else if let Some(repo) = listing.repos.iter_mut().find(|repo| is_changed(repo)) {
+            match change.action {
+                Action::Archive => repo.metadata.archived = true,
+                Action::Unarchive => repo.metadata.archived = false,
+                Action::MakePrivate => repo.metadata.private = true,
+                Action::Delete => {}
+            }
+            repo.status = Status::Unselected;
+            repo.matched_rule = None;
+        }
+        listing.refresh_view();
+        self.load_highlighted_details();
     }
 
     pub fn exit(&mut self) {
@@ -252,11 +1127,28 @@ impl App {
         self.character_index = self.clamp_cursor(cursor_moved_right);
     }
 
+    // The text input the cursor currently edits
+    fn input(&self) -> &String {
+        match self.mode {
+            Mode::Search => &self.search_input,
+            Mode::Confirm => &self.confirm_input,
+            _ => &self.token_input,
+        }
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        match self.mode {
+            Mode::Search => &mut self.search_input,
+            Mode::Confirm => &mut self.confirm_input,
+            _ => &mut self.token_input,
+        }
+    }
+
     pub fn enter_char(&mut self, new_char: char) {
         // Let's not add any more characters if we reached the limit
-        if !self.token_limit_reached() {
+        if self.mode != Mode::Auth || !self.token_limit_reached() {
             let index = self.byte_index();
-            self.token_input.insert(index, new_char);
+            self.input_mut().insert(index, new_char);
             self.move_cursor_right();
         }
     }
@@ -266,11 +1158,11 @@ impl App {
     // Since each character in a string can be contain multiple bytes, it's necessary to calculate
     // the byte index based on the index of the character.
     fn byte_index(&self) -> usize {
-        self.token_input
+        self.input()
             .char_indices()
             .map(|(i, _)| i)
             .nth(self.character_index)
-            .unwrap_or(self.token_input.len())
+            .unwrap_or(self.input().len())
     }
 
     pub fn delete_char(&mut self) {
@@ -284,19 +1176,19 @@ impl App {
             let from_left_to_current_index = current_index - 1;
 
             // Getting all characters before the selected character.
-            let before_char_to_delete = self.token_input.chars().take(from_left_to_current_index);
+            let before_char_to_delete = self.input().chars().take(from_left_to_current_index);
             // Getting all characters after selected character.
-            let after_char_to_delete = self.token_input.chars().skip(current_index);
+            let after_char_to_delete = self.input().chars().skip(current_index);
 
             // Put all characters together except the selected one.
             // By leaving the selected one out, it is forgotten and therefore deleted.
-            self.token_input = before_char_to_delete.chain(after_char_to_delete).collect();
+            *self.input_mut() = before_char_to_delete.chain(after_char_to_delete).collect();
             self.move_cursor_left();
         }
     }
 
     fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.token_input.chars().count())
+        new_cursor_pos.clamp(0, self.input().chars().count())
     }
 
     pub fn reset_cursor(&mut self) {
@@ -308,31 +1200,88 @@ impl App {
         self.reset_cursor();
     }
 
+    fn apply_search(&mut self) {
+        if let Some(listing) = self.listing.as_mut() {
+            listing.set_search(&self.search_input);
+        }
+        self.load_highlighted_details();
+    }
+
+    // Shows all repositories again. Selections live on the repositories themselves, so none of
+    // them are lost.
+    fn clear_search(&mut self) {
+        self.search_input.clear();
+        self.reset_cursor();
+        self.apply_search();
+    }
+
     pub fn select_next(&mut self) {
-        if let Some(github_content) = self.github_content.as_mut() {
-            github_content.list_state.select_next();
+        if let Some(listing) = self.listing.as_mut() {
+            listing.list_state.select_next();
         }
     }
 
     pub fn select_previous(&mut self) {
-        if let Some(github_content) = self.github_content.as_mut() {
-            github_content.list_state.select_previous();
+        if let Some(listing) = self.listing.as_mut() {
+            listing.list_state.select_previous();
         }
     }
 
-    pub fn toggle_status(&mut self) {
-        if let Some(github_content) = self.github_content.as_mut() {
-            if let Some(i) = github_content.list_state.selected() {
-                github_content.repos[i].status = match github_content.repos[i].status {
-                    Status::Selected => Status::Unselected,
-                    Status::Unselected => Status::Selected,
-                };
+    // Marks the highlighted repository for the action, or unmarks it if it already is. Archiving
+    // an archived repository means unarchiving it, private repositories can't be made private.
+    pub fn toggle_status(&mut self, action: Action) {
+        if let Some(listing) = self.listing.as_mut()
+            && let Some(i) = listing.highlighted_index()
+        {
+            let repo = &mut listing.repos[i];
+            if let Some(pattern) =
+                protecting_pattern(&self.config.protected, &repo.owner, &repo.name)
+            {
+                self.status_message = Some(format!(
+                    "{}/{} is protected by '{pattern}' and can't be marked",
+                    repo.owner, repo.name
+                ));
+                return;
             }
+            if !repo.metadata.can_administer() {
+                self.status_message = Some(format!(
+                    "{}/{} needs admin rights to be changed",
+                    repo.owner, repo.name
+                ));
+                return;
+            }
+            if action == Action::MakePrivate && repo.metadata.private {
+                self.status_message = Some(format!("{} is already private", repo.name));
+                return;
+            }
+            let action = match action {
+                Action::Archive | Action::Unarchive if repo.metadata.archived => Action::Unarchive,
+                Action::Archive | Action::Unarchive => Action::Archive,
+                action => action,
+            };
+            if action == Action::Delete
+                && repo.status != Status::Marked(action)
+                && let Some(token_info) = &self.token_info
+                && !token_info.can_delete()
+            {
+                self.status_message = Some(format!(
+                    "The token lacks the {} scope and can't delete repositories",
+                    token_info.forge.delete_scope()
+                ));
+                return;
+            }
+            repo.status = if repo.status == Status::Marked(action) {
+                Status::Unselected
+            } else {
+                Status::Marked(action)
+            };
+            // Marked by hand from now on
+            repo.matched_rule = None;
         }
     }
 
     pub fn token_limit_reached(&self) -> bool {
-        self.token_input.len() > 40
+        self.token_input.len() > MAX_TOKEN_LENGTH
     }
 }
 
@@ -348,8 +1297,8 @@ impl App {
             .split(frame.area());
 
         let body_constraint = match self.mode {
-            Mode::Select => Constraint::Length(15),
-            Mode::Confirm => Constraint::Length(12),
+            Mode::Select | Mode::Search | Mode::Rules => Constraint::Length(20),
+            Mode::Owners | Mode::Confirm | Mode::Summary => Constraint::Length(12),
             _ => Constraint::Length(5),
         };
 
@@ -361,7 +1310,7 @@ impl App {
                 Constraint::Length(1),  // 2: Padding between logo and welcome text
                 body_constraint,        // 3: Dynamic body height
                 Constraint::Length(2),  // 4: Padding between welcome text and footer
-                Constraint::Length(1),  // 5: Footer
+                Constraint::Length(2),  // 5: Footer, long key hints wrap onto a second line
                 Constraint::Fill(1),    // 6: Bottom spacing
             ])
             .split(horizontal_chunks[1]);
@@ -379,26 +1328,150 @@ impl App {
                     character_index,
                     self.token_limit_reached(),
                 );
+                self.footer().render(footer, frame.buffer_mut());
             }
             Mode::Welcome => {
                 self.logo().render(header, frame.buffer_mut());
                 self.description().render(body, frame.buffer_mut());
                 self.footer().render(footer, frame.buffer_mut());
             }
-            Mode::Select => {
-                if !self.waiting_for_repos {
-                    if let Some(github_content) = self.github_content.as_mut() {
-                        render_all_repositories(github_content, body, frame.buffer_mut());
-                        self.footer().render(footer, frame.buffer_mut());
+            Mode::Device => {
+                self.logo().render(header, frame.buffer_mut());
+                if let Some(device_code) = &self.device_code {
+                    render_device_code(device_code, body, frame.buffer_mut());
+                }
+                self.footer().render(footer, frame.buffer_mut());
+            }
+            Mode::Owners => {
+                self.logo().render(header, frame.buffer_mut());
+                render_owners(
+                    &self.owners,
+                    &mut self.owner_state,
+                    body,
+                    frame.buffer_mut(),
+                );
+                self.footer().render(footer, frame.buffer_mut());
+            }
+            Mode::Select | Mode::Search | Mode::Rules => {
+                if !self.waiting_for_repos
+                    && let Some(listing) = self.listing.as_mut()
+                {
+                    let [list_area, details_area] = Layout::horizontal([
+                        Constraint::Percentage(60),
+                        Constraint::Percentage(40),
+                    ])
+                    .areas(body);
+                    let list_area = if self.mode == Mode::Search || !self.search_input.is_empty() {
+                        let [search_area, list_area] =
+                            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)])
+                                .areas(list_area);
+                        draw_search_input(
+                            frame,
+                            search_area,
+                            &self.search_input,
+                            self.character_index as u16,
+                        );
+                        list_area
+                    } else {
+                        list_area
+                    };
+                    render_all_repositories(
+                        listing,
+                        &self.config.protected,
+                        list_area,
+                        frame.buffer_mut(),
+                    );
+
+                    // The list state is only clamped to the list length while rendering
+                    let highlighted = listing.highlighted();
+                    let details = highlighted.and_then(|repo| {
+                        self.repository_client
+                            .as_ref()?
+                            .cached_repo_details(&repo.owner, &repo.name)
+                    });
+                    let details_error = highlighted.and_then(|repo| {
+                        let ((owner, name), error) = self.details_error.as_ref()?;
+                        (*owner == repo.owner && *name == repo.name).then_some(error.as_str())
+                    });
+                    let loading = self.details_task.is_some()
+                        && highlighted.is_some_and(|repo| {
+                            self.details_repo.as_ref().is_some_and(|(owner, name)| {
+                                *owner == repo.owner && *name == repo.name
+                            })
+                        });
+                    render_repository_details(
+                        highlighted,
+                        details.as_ref(),
+                        details_error,
+                        loading,
+                        details_area,
+                        frame.buffer_mut(),
+                    );
+                    self.footer().render(footer, frame.buffer_mut());
+
+                    if self.mode == Mode::Rules {
+                        render_rulesets(frame, &self.config.rulesets, &mut self.ruleset_state);
                     }
                 }
             }
             Mode::Confirm => {
-                if let Some(github_content) = &self.github_content {
-                    render_selected_repositories(frame, &github_content.repos);
+                if let Some(Activity::Changing { done, total }) = self.activity {
+                    let [gauge_area] = Layout::vertical([Constraint::Length(3)])
+                        .flex(Flex::Center)
+                        .areas(body);
+                    render_batch_progress(done, total, gauge_area, frame.buffer_mut());
+                    self.footer().render(footer, frame.buffer_mut());
+                } else if let Some(listing) = &self.listing {
+                    render_selected_repositories(
+                        frame,
+                        &listing.repos,
+                        self.config.backup.as_ref(),
+                    );
+                    if let Some(confirmation) = &self.confirmation {
+                        draw_confirmation_input(
+                            frame,
+                            &confirmation.prompt(),
+                            &self.confirm_input,
+                            self.character_index as u16,
+                        );
+                    }
                     self.footer().render(footer, frame.buffer_mut());
                 }
             }
+            Mode::Summary => {
+                render_summary(&self.reports, body, frame.buffer_mut());
+                self.footer().render(footer, frame.buffer_mut());
+            }
+        }
+
+        // Batches keep running underneath, the popup shows once they are done
+        if let Some(error) = &self.error_state
+            && self.activity.is_none()
+        {
+            render_error(frame, error, self.config.forge);
+        }
+
+        if self.config.dry_run {
+            let [banner] = Layout::vertical([Constraint::Length(1)]).areas(frame.area());
+            render_dry_run_banner(self.config.forge, banner, frame.buffer_mut());
+        }
+        if let Some(token_info) = &self.token_info
+            && !matches!(self.mode, Mode::Welcome | Mode::Auth | Mode::Device)
+        {
+            let [_, status_line] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+            let rate_limit = self
+                .repository_client
+                .as_ref()
+                .map(|repository_client| repository_client.rate_limit())
+                .unwrap_or_default();
+            render_status_line(
+                token_info,
+                &rate_limit,
+                Utc::now(),
+                status_line,
+                frame.buffer_mut(),
+            );
         }
     }
 
@@ -421,17 +1494,29 @@ impl App {
     }
 
     fn description(&self) -> impl Widget {
-        let info_text = vec![
-            Line::from(String::from(
-                "Welcome to knife, a terminal application to delete GitHub repositories.",
+        let forge = self.config.forge.name();
+        let mut info_text = vec![
+            Line::from(format!(
+                "Welcome to knife, a terminal application to delete {forge} repositories."
             )),
-            Line::from(String::from(
-                "After hitting 'Enter', your default browser will open and redirect you to the personal access token (PAT) page on Github.",
+            Line::from(format!(
+                "After hitting 'Enter', your default browser will open and redirect you to the personal access token (PAT) page on {forge}."
             )),
             Line::from(String::from(
                 "Please use the pre-selected settings and copy the PAT.",
             )),
         ];
+        // gh only signs in to GitHub
+        if self.config.forge == ForgeKind::Github {
+            info_text.push(Line::from(String::from(
+                "Already logged in with the GitHub CLI? Press 'g' to use your existing gh login instead.",
+            )));
+        }
+        if self.device_flow_available() {
+            info_text.push(Line::from(String::from(
+                "Or press 'd' to sign in with a one-time code, without creating a token.",
+            )));
+        }
 
         Paragraph::new(Text::from(info_text))
             .alignment(ratatui::layout::Alignment::Center)
@@ -441,47 +1526,543 @@ impl App {
     }
 
     fn footer(&self) -> impl Widget {
-        let footer_text = match self.mode {
-            Mode::Welcome => Line::from(vec![
-                Span::styled("Hit ", Style::default().fg(DARK_GRAY)),
-                Span::styled(
-                    "'Enter'",
-                    Style::default()
-                        .fg(DARK_GRAY)
-                        .add_modifier(Modifier::ITALIC)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Span::styled(
-                    " to get your Token from Github!",
-                    Style::default().fg(DARK_GRAY),
-                ),
-            ]),
-            Mode::Select => Line::from(vec![Span::styled(
-                "Use '↓', '↑', 'j', or 'k' to move; 'Space' to toggle status; and 'Enter' to confirm.",
-                Style::default().fg(DARK_GRAY),
-            )]),
-            Mode::Confirm => Line::from(vec![Span::styled(
-                "Press 'Enter' to delete the selected repo(s)",
-                Style::default().fg(DARK_GRAY),
+        let footer_text = match (&self.activity, &self.status_message) {
+            (Some(activity), message) => {
+                let mut spans = vec![
+                    Span::styled(
+                        format!("{} ", spinner(self.spinner_frame)),
+                        Style::default().fg(LIGHT_RED),
+                    ),
+                    Span::raw(activity.describe()),
+                    Span::styled(" ('Esc' to cancel)", Style::default().fg(DARK_GRAY)),
+                ];
+                // E.g. that the browser didn't open while waiting for the authorization
+                if let Some(message) = message {
+                    spans.push(Span::styled(
+                        format!(" {message}"),
+                        Style::default().fg(LIGHT_RED),
+                    ));
+                }
+                Line::from(spans)
+            }
+            (None, Some(message)) => Line::from(vec![Span::styled(
+                message.clone(),
+                Style::default().fg(LIGHT_RED),
             )]),
-            _ => Line::from("Unknown mode"),
+            (None, None) => self.key_hints(&self.mode),
         };
 
         Paragraph::new(footer_text)
             .alignment(ratatui::layout::Alignment::Center)
             .block(Block::default())
+            .wrap(Wrap { trim: true })
+    }
+
+    fn key_hints(&self, mode: &Mode) -> Line<'static> {
+        match mode {
+            Mode::Welcome => {
+                let forge = self.config.forge;
+                let mut hints = vec![("'Enter'", format!("get your Token from {}", forge.name()))];
+                if forge == ForgeKind::Github {
+                    hints.push(("'g'", String::from("use your gh login")));
+                }
+                if self.device_flow_available() {
+                    hints.push(("'d'", String::from("sign in with a code")));
+                }
+                let text = Style::default().fg(DARK_GRAY);
+                let key = text
+                    .add_modifier(Modifier::ITALIC)
+                    .add_modifier(Modifier::BOLD);
+                let mut spans = vec![Span::styled("Hit ", text)];
+                for (i, (hint_key, action)) in hints.iter().enumerate() {
+                    if i > 0 {
+                        let separator = if i + 1 == hints.len() { " or " } else { ", " };
+                        spans.push(Span::styled(separator, text));
+                    }
+                    spans.push(Span::styled(*hint_key, key));
+                    spans.push(Span::styled(format!(" to {action}"), text));
+                }
+                spans.push(Span::styled("!", text));
+                Line::from(spans)
+            }
+            Mode::Device => Line::from(vec![Span::styled(
+                "Enter the code on GitHub; 'Esc' to cancel.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Auth => Line::from(vec![Span::styled(
+                "Paste your token and press 'Enter'; 'Esc' to go back.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Owners => Line::from(vec![Span::styled(
+                "Use '↓', '↑', 'j', or 'k' to pick an account; 'Enter' to list its repositories; and 'q' to quit.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Select => Line::from(vec![Span::styled(
+                "Use '↓', '↑', 'j', or 'k' to move; 'Space' to mark for deletion; 'a' to mark for archiving; 'p' to mark for making private; '/' to search; '1'-'3' to toggle owner/collaborator/organization repos; 'v' for visibility; 's'/'S' to change the sort order; 'r' to apply a ruleset; 'o' to switch to an organization; and 'Enter' to confirm.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Search => Line::from(vec![Span::styled(
+                "Type to filter by name, description or topics; '↓' or '↑' to move; 'Enter' to keep the filter; and 'Esc' to clear it.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Rules => Line::from(vec![Span::styled(
+                "Use '↓', '↑', 'j', or 'k' to pick a ruleset; 'Enter' to select the repos it matches; and 'Esc' to go back.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Confirm if self.confirmation.is_some() => Line::from(vec![Span::styled(
+                "Type the answer and press 'Enter' to apply the changes; 'Esc' to go back.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Confirm => Line::from(vec![Span::styled(
+                "Press 'Enter' to apply the changes to the marked repo(s); 'Esc' to go back.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+            Mode::Summary => Line::from(vec![Span::styled(
+                "Press 'Enter' or 'Esc' to go back to your repositories; 'q' to quit.",
+                Style::default().fg(DARK_GRAY),
+            )]),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forge::{ChangeResult, ForgeErrorKind, Visibility};
+    use crate::github::RepositoryClient;
+    use crate::test_support::{github_client, repository};
+    use crate::ui::{Permissions, Repository, RepositoryMetadata};
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn app_with_repos(names: &[&str]) -> App {
+        let repos = names.iter().map(|name| repository(name)).collect();
+        let mut app = App::new();
+        app.listing = Some(RepositoryListing::new(
+            "octocat",
+            repos,
+            None,
+            RepoQuery::default(),
+        ));
+        app.mode = Mode::Select;
+        app
+    }
 
     #[test]
     fn test_token_limit_reached_returns_true_when_too_long() {
         let mut app = App::new();
-        app.token_input = "a".repeat(41);
+        app.token_input = "a".repeat(MAX_TOKEN_LENGTH + 1);
         let result = app.token_limit_reached();
         assert!(result);
     }
+
+    #[test]
+    fn test_fine_grained_token_fits() {
+        let mut app = App::new();
+        app.mode = Mode::Auth;
+        let token = format!("github_pat_{}", "A1b2_".repeat(16));
+        for c in token.chars() {
+            app.enter_char(c);
+        }
+        assert_eq!(app.token_input, token);
+        assert!(!app.token_limit_reached());
+    }
+
+    #[test]
+    fn test_toggle_status_in_filtered_view_marks_real_repo() {
+        let mut app = app_with_repos(&["alpha", "beta", "gamma"]);
+        app.mode = Mode::Search;
+        for c in "gam".chars() {
+            app.enter_char(c);
+        }
+        app.apply_search();
+        app.mode = Mode::Select;
+        app.select_next();
+        app.toggle_status(Action::Delete);
+
+        app.clear_search();
+        let listing = app.listing.as_ref().unwrap();
+        assert_eq!(listing.visible.len(), 3);
+        let selected: Vec<_> = listing
+            .repos
+            .iter()
+            .filter(|repo| repo.status == Status::Marked(Action::Delete))
+            .map(|repo| repo.name.as_str())
+            .collect();
+        assert_eq!(selected, vec!["gamma"]);
+        assert_eq!(listing.highlighted().unwrap().name, "gamma");
+    }
+
+    #[test]
+    fn test_task_results_update_listing() {
+        let mut app = app_with_repos(&["alpha", "beta", "gamma"]);
+        app.repos_generation = 2;
+
+        // A page of a listing that was replaced in the meantime
+        app.handle_task_result(TaskResult::Page {
+            generation: 1,
+            result: Ok((Vec::new(), Some(String::from("https://example.com")))),
+        });
+        assert!(app.listing.as_ref().unwrap().next_page.is_none());
+
+        let report = |action, name: &str, outcome, status| Report {
+            change: Change {
+                action,
+                owner: String::from("octocat"),
+                name: name.to_owned(),
+                clone_url: String::new(),
+            },
+            result: ChangeResult {
+                outcome,
+                status: Some(status),
+                message: None,
+            },
+            backup: None,
+        };
+        app.mode = Mode::Confirm;
+        app.activity = Some(Activity::Changing { done: 0, total: 3 });
+        app.handle_task_result(TaskResult::Changed(report(
+            Action::Delete,
+            "alpha",
+            Outcome::Succeeded,
+            StatusCode::NO_CONTENT,
+        )));
+        app.handle_task_result(TaskResult::Changed(report(
+            Action::Delete,
+            "beta",
+            Outcome::Forbidden,
+            StatusCode::FORBIDDEN,
+        )));
+        app.handle_task_result(TaskResult::Changed(report(
+            Action::Archive,
+            "gamma",
+            Outcome::Succeeded,
+            StatusCode::OK,
+        )));
+        assert_eq!(app.activity, Some(Activity::Changing { done: 3, total: 3 }));
+        let listing = app.listing.as_ref().unwrap();
+        assert_eq!(listing.repos.len(), 2);
+        assert_eq!(listing.visible.len(), 2);
+        assert!(listing.repos[1].metadata.archived);
+        assert!(matches!(
+            &app.error_state,
+            Some(Error::Change(report)) if report.change.name == "beta"
+        ));
+
+        app.handle_task_result(TaskResult::BatchFinished);
+        assert_eq!(app.mode, Mode::Summary);
+        assert_eq!(app.reports.len(), 3);
+    }
+
+    #[test]
+    fn test_archive_mark_follows_archived_state() {
+        let mut app = app_with_repos(&["alpha"]);
+        app.select_next();
+        app.toggle_status(Action::Archive);
+        let repo = &app.listing.as_ref().unwrap().repos[0];
+        assert_eq!(repo.status, Status::Marked(Action::Archive));
+
+        // Marking for deletion replaces the archive mark, toggling again unmarks
+        app.toggle_status(Action::Delete);
+        app.toggle_status(Action::Delete);
+        let listing = app.listing.as_mut().unwrap();
+        assert_eq!(listing.repos[0].status, Status::Unselected);
+
+        listing.repos[0].metadata.archived = true;
+        app.toggle_status(Action::Archive);
+        let repo = &app.listing.as_ref().unwrap().repos[0];
+        assert_eq!(repo.status, Status::Marked(Action::Unarchive));
+
+        let listing = app.listing.as_mut().unwrap();
+        listing.repos[0].metadata.private = true;
+        app.toggle_status(Action::MakePrivate);
+        let repo = &app.listing.as_ref().unwrap().repos[0];
+        assert_eq!(repo.status, Status::Marked(Action::Unarchive));
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("alpha is already private")
+        );
+    }
+
+    #[test]
+    fn test_deletion_needs_typed_confirmation() {
+        let mut app = app_with_repos(&["alpha", "beta"]);
+        app.select_next();
+        app.toggle_status(Action::Delete);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::Confirm);
+        assert_eq!(
+            app.confirmation
+                .as_ref()
+                .map(Confirmation::prompt)
+                .as_deref(),
+            Some("Type 'octocat' or 1 to delete 1 repositories")
+        );
+
+        for c in "octo".chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        app.handle_key(KeyEvent::from(KeyCode::Backspace));
+        assert_eq!(app.confirm_input, "oct");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.mode, Mode::Confirm);
+        assert!(app.confirm_input.is_empty());
+        assert!(app.confirmation.is_some());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("That doesn't match, try again")
+        );
+
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(app.mode, Mode::Select);
+        assert!(app.confirmation.is_none());
+    }
+
+    #[test]
+    fn test_protected_repository_cannot_be_marked() {
+        let mut app = app_with_repos(&["dotfiles", "infra-prod"]);
+        app.config.protected = vec![String::from("*-prod")];
+        app.select_next();
+        app.select_next();
+        app.toggle_status(Action::Delete);
+
+        let listing = app.listing.as_ref().unwrap();
+        assert_eq!(listing.highlighted().unwrap().name, "infra-prod");
+        assert!(
+            listing
+                .repos
+                .iter()
+                .all(|repo| repo.status == Status::Unselected)
+        );
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("octocat/infra-prod is protected by '*-prod' and can't be marked")
+        );
+    }
+
+    #[test]
+    fn test_device_flow_needs_client_id_and_returns_on_failure() {
+        let mut app = App::new();
+        app.handle_key(KeyEvent::from(KeyCode::Char('d')));
+        assert!(app.activity.is_none());
+        assert!(
+            app.status_message
+                .as_deref()
+                .unwrap()
+                .starts_with("Set oauth_client_id")
+        );
+
+        app.mode = Mode::Device;
+        app.activity = Some(Activity::WaitingForAuthorization);
+        app.handle_task_result(TaskResult::DeviceToken(Err(
+            "Authorizing knife was cancelled".into(),
+        )));
+        assert_eq!(app.mode, Mode::Welcome);
+        assert!(app.activity.is_none() && app.device_code.is_none());
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Authorizing knife was cancelled")
+        );
+    }
+
+    #[test]
+    fn test_token_without_delete_scope_cannot_mark_deletions() {
+        let mut app = app_with_repos(&["alpha"]);
+        app.token_info = Some(TokenInfo {
+            forge: ForgeKind::Github,
+            login: String::from("octocat"),
+            scopes: Some(vec![String::from("repo")]),
+            expires_at: None,
+        });
+        app.select_next();
+        app.toggle_status(Action::Delete);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("The token lacks the delete_repo scope and can't delete repositories")
+        );
+        app.toggle_status(Action::Archive);
+
+        let listing = app.listing.as_ref().unwrap();
+        assert_eq!(listing.repos[0].status, Status::Marked(Action::Archive));
+    }
+
+    #[tokio::test]
+    async fn test_organization_picker() {
+        let mut app = App::new();
+        app.repository_client = Some(Arc::new(RepositoryClient::with_base_url(
+            "token",
+            "http://127.0.0.1:9",
+        )));
+        app.token_info = Some(TokenInfo {
+            forge: ForgeKind::Github,
+            login: String::from("octocat"),
+            scopes: None,
+            expires_at: None,
+        });
+        app.handle_task_result(TaskResult::Organizations(Ok(vec![String::from("acme")])));
+        assert_eq!(app.mode, Mode::Owners);
+        assert_eq!(app.owners, vec!["octocat", "acme"]);
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(app.activity, Some(Activity::LoadingRepositories));
+        app.cancel_activity();
+
+        let repo = |name: &str, admin| Repository {
+            name: name.to_owned(),
+            owner: String::from("acme"),
+            status: Status::Unselected,
+            metadata: RepositoryMetadata {
+                permissions: Some(Permissions { admin }),
+                ..RepositoryMetadata::default()
+            },
+            matched_rule: None,
+        };
+        let query = RepoQuery {
+            organization: Some(String::from("acme")),
+            ..RepoQuery::default()
+        };
+        let listing = RepositoryListing::new(
+            "acme",
+            vec![repo("experiment", false), repo("prototype", true)],
+            None,
+            query,
+        );
+        app.handle_task_result(TaskResult::Repositories(Ok(listing)));
+        assert_eq!(app.mode, Mode::Select);
+
+        // Only admins may change a repository
+        app.select_next();
+        app.toggle_status(Action::Delete);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("acme/experiment needs admin rights to be changed")
+        );
+        app.select_next();
+        app.toggle_status(Action::Delete);
+        let repos = &app.listing.as_ref().unwrap().repos;
+        assert_eq!(repos[0].status, Status::Unselected);
+        assert_eq!(repos[1].status, Status::Marked(Action::Delete));
+
+        app.handle_key(KeyEvent::from(KeyCode::Char('o')));
+        assert_eq!(app.mode, Mode::Owners);
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert_eq!(app.mode, Mode::Select);
+    }
+
+    #[test]
+    fn test_welcome_hints_follow_the_forge() {
+        let hints = |app: &App| app.key_hints(&Mode::Welcome).to_string();
+        let mut app = App::new();
+        assert_eq!(
+            hints(&app),
+            "Hit 'Enter' to get your Token from GitHub or 'g' to use your gh login!"
+        );
+        app.config.oauth_client_id = Some(String::from("Iv1.0123456789abcdef"));
+        assert_eq!(
+            hints(&app),
+            "Hit 'Enter' to get your Token from GitHub, 'g' to use your gh login or 'd' to sign in with a code!"
+        );
+        app.config.forge = ForgeKind::Gitlab;
+        assert_eq!(hints(&app), "Hit 'Enter' to get your Token from GitLab!");
+    }
+
+    #[tokio::test]
+    async fn test_switching_owners_keeps_the_filters() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/orgs/acme/repos"))
+            .and(query_param("type", "private"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut app = app_with_repos(&["alpha"]);
+        app.repository_client = Some(Arc::new(github_client(&server)));
+        app.owners = vec![String::from("octocat"), String::from("acme")];
+        let listing = app.listing.as_mut().unwrap();
+        listing.query.visibility = Visibility::Private;
+        listing.query.toggle_affiliation(Affiliation::Collaborator);
+
+        app.load_owner(1);
+        let result = app.task_receiver.recv().await.unwrap();
+        app.handle_task_result(result);
+        let query = &app.listing.as_ref().unwrap().query;
+        assert_eq!(query.organization.as_deref(), Some("acme"));
+        assert_eq!(query.visibility, Visibility::Private);
+        assert_eq!(
+            query.affiliations,
+            vec![Affiliation::Owner, Affiliation::Collaborator]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_details_follow_the_highlighted_repository() {
+        let mut app = App::new();
+        app.repository_client = Some(Arc::new(RepositoryClient::with_base_url(
+            "token",
+            "http://127.0.0.1:9",
+        )));
+        let listing = app_with_repos(&["alpha", "beta"]).listing.unwrap();
+        app.handle_task_result(TaskResult::Repositories(Ok(listing)));
+        let requested = |app: &App| app.details_repo.as_ref().map(|(_, name)| name.clone());
+        assert_eq!(requested(&app), None);
+        app.handle_key(KeyEvent::from(KeyCode::Char('j')));
+        assert_eq!(requested(&app).as_deref(), Some("alpha"));
+
+        // Filtering moves the highlight to the first match
+        app.search_input = String::from("beta");
+        app.apply_search();
+        assert_eq!(requested(&app).as_deref(), Some("beta"));
+        // A late answer for the previous one doesn't end the request for the highlighted one
+        app.handle_task_result(TaskResult::Details {
+            owner: String::from("octocat"),
+            name: String::from("alpha"),
+            result: Err(ForgeErrorKind::NotFound {
+                message: String::from("Not Found"),
+            }
+            .on(ForgeKind::Github)),
+        });
+        assert!(app.details_task.is_some());
+        assert_eq!(requested(&app).as_deref(), Some("beta"));
+        assert_eq!(app.details_error, None);
+        // A failure is shown next to the repository, not in a popup
+        app.handle_task_result(TaskResult::Details {
+            owner: String::from("octocat"),
+            name: String::from("beta"),
+            result: Err(ForgeErrorKind::NotFound {
+                message: String::from("Not Found"),
+            }
+            .on(ForgeKind::Github)),
+        });
+        assert!(app.details_task.is_none());
+        assert!(app.error_state.is_none());
+        assert_eq!(
+            app.details_error,
+            Some((
+                (String::from("octocat"), String::from("beta")),
+                String::from("Not Found")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_error_popup_needs_dismissing() {
+        let mut app = app_with_repos(&["alpha", "beta"]);
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let Some(error) = &app.error_state else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.title(), "Nothing selected");
+
+        // Keys don't reach the list while the popup is open
+        app.handle_key(KeyEvent::from(KeyCode::Char(' ')));
+        assert!(app.error_state.is_some());
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.error_state.is_none());
+        assert_eq!(app.mode, Mode::Select);
+    }
 }
@@ -0,0 +1,254 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::{
+    batch::Change,
+    forge::{BoxError, Forge},
+};
+
+// Lists exported next to the mirror, as file name and endpoint below `/repos/{owner}/{repo}`
+const EXPORTS: [(&str, &str); 3] = [
+    ("issues.json", "issues?state=all"),
+    ("pulls.json", "pulls?state=all"),
+    ("releases.json", "releases"),
+];
+
+// The `[backup]` table of the config. Repositories are only deleted once they are backed up.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Backup {
+    pub directory: PathBuf,
+    // Also save issues, pull requests and releases as JSON and mirror the wiki
+    #[serde(default)]
+    pub export: bool,
+}
+
+impl Backup {
+    // Mirrors the repository into `<directory>/<owner>/<name>-<timestamp>` and returns that
+    // folder. Fails unless the mirror could be cloned and verified and the exports were written,
+    // the folder is removed again then so it isn't mistaken for a backup.
+    pub async fn run(
+        &self,
+        repository_client: &dyn Forge,
+        change: &Change,
+        now: DateTime<Utc>,
+    ) -> Result<PathBuf, BoxError> {
+        let folder = self.directory.join(&change.owner).join(format!(
+            "{}-{}",
+            change.name,
+            now.format("%Y%m%dT%H%M%SZ")
+        ));
+        tokio::fs::create_dir_all(&folder).await?;
+
+        match self.write(repository_client, change, &folder).await {
+            Ok(()) => Ok(folder),
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&folder).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn write(
+        &self,
+        repository_client: &dyn Forge,
+        change: &Change,
+        folder: &Path,
+    ) -> Result<(), BoxError> {
+        let mirror = folder.join("mirror.git");
+        clone_mirror(repository_client, &change.clone_url, &mirror).await?;
+        verify(&mirror).await?;
+
+        if self.export {
+            for (file, endpoint) in EXPORTS {
+                let items = repository_client
+                    .get_all_items(&change.owner, &change.name, endpoint)
+                    .await?;
+                tokio::fs::write(folder.join(file), serde_json::to_vec_pretty(&items)?).await?;
+            }
+            // GitHub only creates the wiki repository once the first page is written, so a
+            // missing wiki is not an error
+            if let Some(base) = change.clone_url.strip_suffix(".git") {
+                let wiki_url = format!("{base}.wiki.git");
+                let _ = clone_mirror(repository_client, &wiki_url, &folder.join("wiki.git")).await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn clone_mirror(
+    repository_client: &dyn Forge,
+    url: &str,
+    destination: &Path,
+) -> Result<(), BoxError> {
+    let mut command = Command::new("git");
+    command
+        .arg("clone")
+        .arg("--mirror")
+        .arg("--quiet")
+        .arg(url)
+        .arg(destination)
+        // Never ask for credentials, there is no terminal to type them into
+        .env("GIT_TERMINAL_PROMPT", "0");
+    if url.starts_with("https://") {
+        // Passed through the environment so the token doesn't show up in the process list
+        let credentials = STANDARD.encode(format!("x-access-token:{}", repository_client.token()));
+        command
+            .env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+            .env(
+                "GIT_CONFIG_VALUE_0",
+                format!("Authorization: Basic {credentials}"),
+            );
+    }
+
+    let output = command.output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git clone --mirror {url} failed: {}", stderr.trim()).into());
+    }
+    Ok(())
+}
+
+// A mirror with missing objects would only be noticed when it is too late
+async fn verify(mirror: &Path) -> Result<(), BoxError> {
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(mirror)
+        .args(["fsck", "--connectivity-only", "--no-progress"])
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Verifying {} failed: {}", mirror.display(), stderr.trim()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::Action;
+    use crate::github::RepositoryClient;
+    use crate::test_support::{self, github_client};
+    use chrono::TimeZone;
+    use serde_json::json;
+    use std::process;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = process::Command::new("git")
+            .current_dir(dir)
+            .args([
+                "-c",
+                "user.name=knife",
+                "-c",
+                "user.email=knife@example.com",
+            ])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    // A bare repository with a single commit, as GitHub would serve it
+    fn bare_repository(dir: &Path) -> PathBuf {
+        let work = dir.join("work");
+        std::fs::create_dir(&work).unwrap();
+        git(&work, &["init", "--quiet"]);
+        std::fs::write(work.join("README.md"), "knife").unwrap();
+        git(&work, &["add", "README.md"]);
+        git(&work, &["commit", "--quiet", "-m", "Initial commit"]);
+        let bare = dir.join("repo.git");
+        git(
+            dir,
+            &["clone", "--bare", "--quiet", "work", bare.to_str().unwrap()],
+        );
+        bare
+    }
+
+    fn change(clone_url: &str) -> Change {
+        Change {
+            clone_url: clone_url.to_owned(),
+            ..test_support::change(Action::Delete, "repo")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backup_mirrors_and_exports() {
+        let remote = tempfile::tempdir().unwrap();
+        let bare = bare_repository(remote.path());
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/repo/issues"))
+            .and(query_param("state", "all"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "number": 1 }])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/repo/pulls"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/repo/releases"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "tag_name": "v1" }])))
+            .mount(&server)
+            .await;
+
+        let directory = tempfile::tempdir().unwrap();
+        let backup = Backup {
+            directory: directory.path().to_owned(),
+            export: true,
+        };
+        let client = github_client(&server);
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let folder = backup
+            .run(&client, &change(bare.to_str().unwrap()), now)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            folder,
+            directory.path().join("octocat/repo-20240601T120000Z")
+        );
+        git(&folder, &["--git-dir", "mirror.git", "log", "--oneline"]);
+        let issues = std::fs::read_to_string(folder.join("issues.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&issues).unwrap(),
+            json!([{ "number": 1 }])
+        );
+        assert!(folder.join("releases.json").exists());
+        // The local repository has no wiki
+        assert!(!folder.join("wiki.git").exists());
+    }
+
+    #[tokio::test]
+    async fn test_backup_fails_without_mirror() {
+        let directory = tempfile::tempdir().unwrap();
+        let backup = Backup {
+            directory: directory.path().to_owned(),
+            export: false,
+        };
+        let client = RepositoryClient::with_base_url("token", "http://127.0.0.1:9");
+        let missing = directory.path().join("missing.git");
+        let result = backup
+            .run(&client, &change(missing.to_str().unwrap()), Utc::now())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("git clone --mirror")
+        );
+        // Only completed backups are left behind
+        let owner = directory.path().join("octocat");
+        assert_eq!(std::fs::read_dir(owner).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,390 @@
+use chrono::Utc;
+use futures::StreamExt;
+use serde::Serialize;
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    backup::Backup,
+    forge::{ChangeResult, Forge, Outcome},
+    protected::protecting_pattern,
+    task::TaskResult,
+};
+
+// What can be done to a marked repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Delete,
+    Archive,
+    Unarchive,
+    MakePrivate,
+}
+
+impl Action {
+    // In the order the Confirm screen and the summary list them
+    pub const ALL: [Action; 4] = [
+        Action::Delete,
+        Action::Archive,
+        Action::Unarchive,
+        Action::MakePrivate,
+    ];
+
+    // Heading of the group on the Confirm screen
+    pub fn pending(&self) -> &'static str {
+        match self {
+            Action::Delete => "Will be deleted",
+            Action::Archive => "Will be archived",
+            Action::Unarchive => "Will be unarchived",
+            Action::MakePrivate => "Will become private",
+        }
+    }
+
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::Archive => "archive",
+            Action::Unarchive => "unarchive",
+            Action::MakePrivate => "make private",
+        }
+    }
+
+    // Completes "... 2 of 3" in the summary
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            Action::Delete => "deleted",
+            Action::Archive => "archived",
+            Action::Unarchive => "unarchived",
+            Action::MakePrivate => "made private",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub action: Action,
+    pub owner: String,
+    pub name: String,
+    // Where to mirror the repository from before it gets deleted
+    pub clone_url: String,
+}
+
+// What happened to one repository of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub change: Change,
+    pub result: ChangeResult,
+    // Folder of the backup taken before deleting
+    pub backup: Option<PathBuf>,
+}
+
+// How a batch runs, taken from the config
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // Number of repositories changed at the same time
+    pub concurrency: usize,
+    pub backup: Option<Backup>,
+    // Patterns of repositories that must not be changed, see `protected::protecting_pattern`
+    pub protected: Vec<String>,
+}
+
+// Applies the changes with at most `concurrency` requests in flight. Every repository is reported
+// as soon as it is done, failures don't stop the batch. With a backup configured, repositories are
+// only deleted once their backup succeeded.
+pub async fn run(
+    repository_client: Arc<dyn Forge>,
+    changes: Vec<Change>,
+    options: Options,
+    sender: UnboundedSender<TaskResult>,
+) {
+    futures::stream::iter(changes)
+        .map(|change| {
+            let repository_client = repository_client.as_ref();
+            let options = &options;
+            async move {
+                let (owner, name) = (&change.owner, &change.name);
+                // Protected repositories can't be marked, this is the last line of defense
+                if let Some(pattern) = protecting_pattern(&options.protected, owner, name) {
+                    let result = ChangeResult {
+                        outcome: Outcome::Protected,
+                        status: None,
+                        message: Some(format!("matches the protected pattern '{pattern}'")),
+                    };
+                    return Report {
+                        change,
+                        result,
+                        backup: None,
+                    };
+                }
+
+                let mut backup_folder = None;
+                if change.action == Action::Delete
+                    && let Some(backup) = &options.backup
+                    && !repository_client.dry_run()
+                {
+                    match backup.run(repository_client, &change, Utc::now()).await {
+                        Ok(folder) => backup_folder = Some(folder),
+                        Err(e) => {
+                            let result = ChangeResult {
+                                outcome: Outcome::BackupFailed,
+                                status: None,
+                                message: Some(e.to_string()),
+                            };
+                            return Report {
+                                change,
+                                result,
+                                backup: None,
+                            };
+                        }
+                    }
+                }
+
+                let mut result = match change.action {
+                    Action::Delete => repository_client.delete_repo(owner, name).await,
+                    Action::Archive => repository_client.set_archived(owner, name, true).await,
+                    Action::Unarchive => repository_client.set_archived(owner, name, false).await,
+                    Action::MakePrivate => repository_client.set_private(owner, name, true).await,
+                };
+                if result.outcome == Outcome::DryRun
+                    && change.action == Action::Delete
+                    && let Some(backup) = &options.backup
+                {
+                    let request = result.message.take().unwrap_or_default();
+                    result.message = Some(format!(
+                        "would back up to {}, {request}",
+                        backup.directory.display()
+                    ));
+                }
+                Report {
+                    change,
+                    result,
+                    backup: backup_folder,
+                }
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .for_each(|report| {
+            let _ = sender.send(TaskResult::Changed(report));
+            async {}
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{change, github_client};
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_run_reports_every_repository() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/gone"))
+            .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_millis(50)))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/locked"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "message": "Must have admin rights to Repository.",
+                "documentation_url": "https://docs.github.com/rest/repos/repos#delete-a-repository"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/limited"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .set_body_json(json!({ "message": "API rate limit exceeded" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/missing"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_json(json!({ "message": "Not Found" })),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/repos/octocat/frozen"))
+            .and(body_json(json!({ "archived": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "archived": true })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/repos/octocat/hidden"))
+            .and(body_json(json!({ "private": true })))
+            .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+                "message": "Visibility can't be private. Please upgrade your plan."
+            })))
+            .mount(&server)
+            .await;
+
+        let changes = vec![
+            change(Action::Delete, "gone"),
+            change(Action::Delete, "locked"),
+            change(Action::Delete, "limited"),
+            change(Action::Delete, "missing"),
+            change(Action::Archive, "frozen"),
+            change(Action::MakePrivate, "hidden"),
+        ];
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = Arc::new(github_client(&server));
+        let options = Options {
+            concurrency: 2,
+            ..Options::default()
+        };
+        run(client, changes, options, sender).await;
+
+        let mut reports = Vec::new();
+        while let Ok(TaskResult::Changed(report)) = receiver.try_recv() {
+            reports.push((report.change.name, report.result));
+        }
+        reports.sort_by(|a, b| a.0.cmp(&b.0));
+        let outcomes: Vec<_> = reports
+            .iter()
+            .map(|(name, result)| (name.as_str(), result.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("frozen", Outcome::Succeeded),
+                ("gone", Outcome::Succeeded),
+                ("hidden", Outcome::Failed),
+                ("limited", Outcome::RateLimited),
+                ("locked", Outcome::Forbidden),
+                ("missing", Outcome::NotFound),
+            ]
+        );
+        let (_, locked) = &reports[4];
+        assert_eq!(locked.status, Some(StatusCode::FORBIDDEN));
+        assert_eq!(
+            locked.message.as_deref(),
+            Some("Must have admin rights to Repository.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_refuses_deletion_without_backup() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let directory = tempfile::tempdir().unwrap();
+        let backup = Backup {
+            directory: directory.path().to_owned(),
+            export: false,
+        };
+        let missing = directory.path().join("missing.git");
+        let changes = vec![Change {
+            action: Action::Delete,
+            owner: String::from("octocat"),
+            name: String::from("missing"),
+            clone_url: missing.to_string_lossy().into_owned(),
+        }];
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = Arc::new(github_client(&server));
+        let options = Options {
+            concurrency: 1,
+            backup: Some(backup),
+            ..Options::default()
+        };
+        run(client, changes, options, sender).await;
+
+        let Ok(TaskResult::Changed(report)) = receiver.try_recv() else {
+            panic!("expected a report");
+        };
+        assert_eq!(report.result.outcome, Outcome::BackupFailed);
+        assert!(report.backup.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_sends_no_changes() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let changes = vec![
+            change(Action::Delete, "repo"),
+            change(Action::Archive, "repo"),
+        ];
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = Arc::new(github_client(&server).with_dry_run(true));
+        // A backup would create the directory and clone the repository into it
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path().join("backups");
+        let options = Options {
+            concurrency: 2,
+            backup: Some(Backup {
+                directory: directory.clone(),
+                export: true,
+            }),
+            ..Options::default()
+        };
+        run(client, changes, options, sender).await;
+
+        let mut messages = Vec::new();
+        while let Ok(TaskResult::Changed(report)) = receiver.try_recv() {
+            assert_eq!(report.result.outcome, Outcome::DryRun);
+            messages.extend(report.result.message);
+        }
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec![
+                format!(
+                    "would back up to {}, would send DELETE /repos/octocat/repo",
+                    directory.display()
+                ),
+                String::from("would send PATCH /repos/octocat/repo {\"archived\":true}"),
+            ]
+        );
+        assert!(!directory.exists());
+    }
+
+    #[tokio::test]
+    async fn test_run_skips_protected_repositories() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let changes = vec![change(Action::Delete, "dotfiles")];
+        let options = Options {
+            concurrency: 1,
+            protected: vec![String::from("dotfiles")],
+            ..Options::default()
+        };
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = Arc::new(github_client(&server));
+        run(client, changes, options, sender).await;
+
+        let Ok(TaskResult::Changed(report)) = receiver.try_recv() else {
+            panic!("expected a report");
+        };
+        assert_eq!(report.result.outcome, Outcome::Protected);
+    }
+}
@@ -0,0 +1,464 @@
+use chrono::{NaiveDate, NaiveTime};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use std::{path::PathBuf, process::ExitCode, sync::Arc};
+use tokio::sync::mpsc;
+
+use crate::{
+    batch::{self, Action, Change, Report},
+    config::Config,
+    forge::{self, BoxError, Forge, ForgeError, ForgeKind, Outcome, RepoQuery},
+    protected::protecting_pattern,
+    task::TaskResult,
+    ui::{Repository, RepositoryMetadata},
+};
+
+// Exit codes of the subcommands, clap itself exits with 2 on invalid arguments
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+// The batch ran, but not every repository could be changed
+const EXIT_PARTIAL: u8 = 3;
+
+/// A terminal application to find and delete your old, deserted GitHub or GitLab repositories
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Go through the whole flow without changing anything on GitHub or GitLab
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Read the token from this file instead of GITHUB_TOKEN, GH_TOKEN or GITLAB_TOKEN, "-" for stdin
+    #[arg(long, global = true, value_name = "PATH")]
+    pub token_file: Option<PathBuf>,
+
+    /// Where the repositories live
+    #[arg(long, global = true, env = "KNIFE_FORGE", value_enum)]
+    pub forge: Option<ForgeKind>,
+
+    /// GitHub Enterprise Server or self-managed GitLab host, e.g. github.example.com
+    #[arg(long, global = true, env = "KNIFE_HOST", value_name = "HOST")]
+    pub host: Option<String>,
+
+    /// REST API base URL, defaults to https://HOST/api/v3 on GitHub Enterprise Server and /api/v4 on GitLab
+    #[arg(long, global = true, env = "KNIFE_API_URL", value_name = "URL")]
+    pub api_url: Option<String>,
+
+    // Without a subcommand knife starts the terminal UI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List your repositories
+    List {
+        /// Print JSON instead of one line per repository
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete repositories
+    Delete(Target),
+    /// Archive repositories
+    Archive(Target),
+}
+
+// Which repositories a subcommand changes. Names and `--pushed-before` can be combined.
+#[derive(Debug, Args)]
+pub struct Target {
+    /// Repository names, prefixed with "owner/" for repositories of other owners
+    names: Vec<String>,
+
+    /// Only repositories without a push since this date, e.g. 2021-01-01
+    #[arg(long, value_name = "DATE")]
+    pushed_before: Option<NaiveDate>,
+
+    /// Confirm the changes, nothing happens without it
+    #[arg(long)]
+    yes: bool,
+
+    /// Print JSON instead of one line per repository
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct ListedRepository<'a> {
+    owner: &'a str,
+    name: &'a str,
+    #[serde(flatten)]
+    metadata: &'a RepositoryMetadata,
+}
+
+#[derive(Serialize)]
+struct ReportOutput<'a> {
+    owner: &'a str,
+    name: &'a str,
+    action: Action,
+    outcome: Outcome,
+    status: Option<u16>,
+    message: Option<&'a str>,
+    backup: Option<String>,
+}
+
+pub async fn run(command: Command, token: Option<String>, config: Config) -> ExitCode {
+    match execute(command, token, config).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("knife: {e}");
+            if let Some(error) = e.downcast_ref::<ForgeError>() {
+                eprintln!("knife: {}", error.hint());
+            }
+            ExitCode::from(EXIT_FAILED)
+        }
+    }
+}
+
+async fn execute(
+    command: Command,
+    token: Option<String>,
+    config: Config,
+) -> Result<ExitCode, BoxError> {
+    let Some(token) = token else {
+        eprintln!(
+            "knife: set {} or pass --token-file to use knife without the terminal UI",
+            config.forge.token_variables().join(" or ")
+        );
+        return Ok(ExitCode::from(EXIT_USAGE));
+    };
+    let repository_client = forge::connect(&config.host(), &token, config.dry_run);
+    let token_info = repository_client.check_token().await?;
+    // Every deletion would fail with 403, better say why before trying
+    if matches!(command, Command::Delete(_)) && !token_info.can_delete() {
+        eprintln!(
+            "knife: the token lacks the {} scope",
+            config.forge.delete_scope()
+        );
+        return Ok(ExitCode::from(EXIT_FAILED));
+    }
+    let repos = all_repositories(repository_client.as_ref(), &token_info.login).await?;
+
+    match command {
+        Command::List { json } => {
+            list(&repos, json)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Delete(target) => {
+            change(repository_client, repos, Action::Delete, target, config).await
+        }
+        Command::Archive(target) => {
+            change(repository_client, repos, Action::Archive, target, config).await
+        }
+    }
+}
+
+async fn all_repositories(
+    repository_client: &dyn Forge,
+    owner: &str,
+) -> Result<Vec<Repository>, BoxError> {
+    let content = repository_client
+        .get_repos(owner, &RepoQuery::default())
+        .await?;
+    let mut repos = content.repos;
+    let mut next_page = content.next_page;
+    while let Some(url) = next_page {
+        let (page, next) = repository_client.get_repos_page(&url).await?;
+        repos.extend(page);
+        next_page = next;
+    }
+    Ok(repos)
+}
+
+fn list(repos: &[Repository], json: bool) -> Result<(), BoxError> {
+    if json {
+        let listed: Vec<ListedRepository> = repos
+            .iter()
+            .map(|repo| ListedRepository {
+                owner: &repo.owner,
+                name: &repo.name,
+                metadata: &repo.metadata,
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+        return Ok(());
+    }
+
+    for repo in repos {
+        let pushed_at = repo
+            .metadata
+            .pushed_at
+            .map(|pushed_at| pushed_at.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| String::from("-"));
+        println!(
+            "{}/{}\t{pushed_at}\t{}",
+            repo.owner, repo.name, repo.metadata.stargazers_count
+        );
+    }
+    Ok(())
+}
+
+async fn change(
+    repository_client: Arc<dyn Forge>,
+    repos: Vec<Repository>,
+    action: Action,
+    target: Target,
+    config: Config,
+) -> Result<ExitCode, BoxError> {
+    if target.names.is_empty() && target.pushed_before.is_none() {
+        eprintln!("knife: name the repositories or pass --pushed-before");
+        return Ok(ExitCode::from(EXIT_USAGE));
+    }
+    let unknown: Vec<&String> = target
+        .names
+        .iter()
+        .filter(|name| !repos.iter().any(|repo| is_named(repo, name)))
+        .collect();
+    if !unknown.is_empty() {
+        let unknown: Vec<&str> = unknown.iter().map(|name| name.as_str()).collect();
+        eprintln!("knife: unknown repositories: {}", unknown.join(", "));
+        return Ok(ExitCode::from(EXIT_USAGE));
+    }
+
+    let pushed_before = target
+        .pushed_before
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc());
+    let mut changes = Vec::new();
+    for repo in &repos {
+        let named = target.names.is_empty() || target.names.iter().any(|name| is_named(repo, name));
+        let stale = pushed_before.is_none_or(|date| {
+            repo.metadata
+                .pushed_at
+                .is_none_or(|pushed_at| pushed_at < date)
+        });
+        let unchanged = action != Action::Archive || !repo.metadata.archived;
+        if !(named && stale && unchanged) {
+            continue;
+        }
+        if let Some(pattern) = protecting_pattern(&config.protected, &repo.owner, &repo.name) {
+            eprintln!(
+                "knife: skipping {}/{}, it is protected by '{pattern}'",
+                repo.owner, repo.name
+            );
+            continue;
+        }
+        if !repo.metadata.can_administer() {
+            eprintln!(
+                "knife: skipping {}/{}, it needs admin rights",
+                repo.owner, repo.name
+            );
+            continue;
+        }
+        changes.push(Change {
+            action,
+            owner: repo.owner.clone(),
+            name: repo.name.clone(),
+            clone_url: repo.metadata.clone_url.clone(),
+        });
+    }
+
+    if !target.yes && !config.dry_run {
+        for change in &changes {
+            eprintln!(
+                "would be {}: {}/{}",
+                action.past_tense(),
+                change.owner,
+                change.name
+            );
+        }
+        eprintln!(
+            "knife: pass --yes to {} {} repositories",
+            action.verb(),
+            changes.len()
+        );
+        return Ok(ExitCode::from(EXIT_USAGE));
+    }
+
+    let options = batch::Options {
+        concurrency: config.concurrency,
+        backup: config.backup,
+        protected: config.protected,
+    };
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    batch::run(repository_client, changes, options, sender).await;
+    let mut reports = Vec::new();
+    while let Some(TaskResult::Changed(report)) = receiver.recv().await {
+        reports.push(report);
+    }
+
+    print_reports(&reports, target.json)?;
+    let failed = reports
+        .iter()
+        .any(|report| !matches!(report.result.outcome, Outcome::Succeeded | Outcome::DryRun));
+    Ok(if failed {
+        ExitCode::from(EXIT_PARTIAL)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+// Names match case insensitively like on GitHub, with or without the owner
+fn is_named(repo: &Repository, name: &str) -> bool {
+    // GitLab namespaces can be nested, the repository is after the last slash
+    match name.rsplit_once('/') {
+        Some((owner, name)) => {
+            repo.owner.eq_ignore_ascii_case(owner) && repo.name.eq_ignore_ascii_case(name)
+        }
+        None => repo.name.eq_ignore_ascii_case(name),
+    }
+}
+
+fn print_reports(reports: &[Report], json: bool) -> Result<(), BoxError> {
+    if json {
+        let output: Vec<ReportOutput> = reports
+            .iter()
+            .map(|report| ReportOutput {
+                owner: &report.change.owner,
+                name: &report.change.name,
+                action: report.change.action,
+                outcome: report.result.outcome,
+                status: report.result.status.map(|status| status.as_u16()),
+                message: report.result.message.as_deref(),
+                backup: report
+                    .backup
+                    .as_ref()
+                    .map(|folder| folder.display().to_string()),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    for report in reports {
+        let mut line = format!(
+            "{}\t{}/{}",
+            report.result.outcome.label(),
+            report.change.owner,
+            report.change.name
+        );
+        if let Some(message) = &report.result.message {
+            line.push_str(&format!("\t{message}"));
+        }
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{github_client, repository};
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_subcommands_parse() {
+        let cli = Cli::try_parse_from([
+            "knife",
+            "archive",
+            "--pushed-before",
+            "2021-01-01",
+            "--yes",
+            "--dry-run",
+            "--token-file",
+            "-",
+        ])
+        .unwrap();
+        assert!(cli.dry_run);
+        assert_eq!(cli.token_file, Some(PathBuf::from("-")));
+        let Some(Command::Archive(target)) = cli.command else {
+            panic!("expected the archive subcommand");
+        };
+        assert!(target.yes);
+        assert_eq!(target.pushed_before, NaiveDate::from_ymd_opt(2021, 1, 1));
+
+        let cli = Cli::try_parse_from(["knife"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(Cli::try_parse_from(["knife", "archive", "--pushed-before", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_is_named_with_or_without_owner() {
+        let repo = Repository {
+            owner: String::from("strbrgr"),
+            ..repository("Knife")
+        };
+        assert!(is_named(&repo, "knife"));
+        assert!(is_named(&repo, "strbrgr/knife"));
+        assert!(!is_named(&repo, "octocat/knife"));
+
+        let nested = Repository {
+            name: String::from("old"),
+            owner: String::from("group/sub"),
+            ..repo
+        };
+        assert!(is_named(&nested, "group/sub/old"));
+        assert!(!is_named(&nested, "group/old"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_needs_yes_and_reports_failures() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/alpha"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/beta"))
+            .respond_with(
+                ResponseTemplate::new(403).set_body_json(json!({ "message": "Forbidden" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let repos: Vec<Repository> = ["alpha", "beta", "gamma"].map(repository).to_vec();
+        let client: Arc<dyn Forge> = Arc::new(github_client(&server));
+        let target = |yes| {
+            Cli::try_parse_from(["knife", "delete", "alpha", "beta"])
+                .map(|cli| match cli.command {
+                    Some(Command::Delete(target)) => Target { yes, ..target },
+                    _ => panic!("expected the delete subcommand"),
+                })
+                .unwrap()
+        };
+
+        let code = change(
+            client.clone(),
+            repos.clone(),
+            Action::Delete,
+            target(false),
+            Config::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, ExitCode::from(EXIT_USAGE));
+
+        let unknown = Target {
+            names: vec![String::from("delta")],
+            ..target(true)
+        };
+        let code = change(
+            client.clone(),
+            repos.clone(),
+            Action::Delete,
+            unknown,
+            Config::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, ExitCode::from(EXIT_USAGE));
+
+        let code = change(
+            client,
+            repos,
+            Action::Delete,
+            target(true),
+            Config::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, ExitCode::from(EXIT_PARTIAL));
+    }
+}
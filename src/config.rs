@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use std::{env, fs, io, path::PathBuf};
+
+use crate::{backup::Backup, forge::ForgeKind, host::Host, rules::Ruleset};
+
+// Settings read from `$XDG_CONFIG_HOME/knife/config.toml`, falling back to
+// `~/.config/knife/config.toml`. `KNIFE_CONFIG` points knife at a different file.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub rulesets: Vec<Ruleset>,
+    // Number of repositories changed at the same time by a batch
+    pub concurrency: usize,
+    pub backup: Option<Backup>,
+    // Names or glob patterns of repositories knife refuses to change
+    pub protected: Vec<String>,
+    // Larger batches need the name of every deleted repository typed
+    pub confirm_names_above: usize,
+    // Client ID of the OAuth app used to sign in through the device flow
+    pub oauth_client_id: Option<String>,
+    // Whether the repositories live on GitHub or GitLab
+    pub forge: ForgeKind,
+    // GitHub Enterprise Server or GitLab host, github.com or gitlab.com if not set
+    pub host: Option<String>,
+    // Only needed if the API isn't at the usual place of the host
+    pub api_url: Option<String>,
+    // Only set from the command line, see `cli::Cli`
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rulesets: Vec::new(),
+            concurrency: 4,
+            backup: None,
+            confirm_names_above: 10,
+            protected: Vec::new(),
+            oauth_client_id: None,
+            forge: ForgeKind::Github,
+            host: None,
+            api_url: None,
+            dry_run: false,
+        }
+    }
+}
+
+impl Config {
+    // Settings that don't work together, checked once the flags were applied
+    pub fn validate(&self) -> Result<(), String> {
+        if self.backup.as_ref().is_some_and(|backup| backup.export)
+            && !self.forge.supports_exports()
+        {
+            return Err(format!(
+                "Backup exports are not supported on {}, remove `export` from `[backup]`",
+                self.forge.name()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn host(&self) -> Host {
+        Host::new(
+            self.forge,
+            self.host.as_deref().unwrap_or(self.forge.default_host()),
+            self.api_url.as_deref(),
+        )
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("KNIFE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("knife").join("config.toml"))
+}
+
+// A missing config file is not an error, knife then runs with the defaults
+pub fn load() -> Result<Config, Box<dyn std::error::Error>> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => parse(&content)
+            .map_err(|e| format!("Invalid config file {}: {e}", path.display()).into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("Could not read config file {}: {e}", path.display()).into()),
+    }
+}
+
+pub fn parse(content: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(content)
+}
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::ui::{Repository, display_name};
+
+// Repositories pushed to within this many days are probably still in use
+const RECENT_PUSH_DAYS: i64 = 90;
+
+// What the user has to type before a batch with deletions runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Confirmation {
+    // Either the owner name or the number of deleted repositories
+    Simple {
+        owner: String,
+        count: usize,
+    },
+    // The name of every deleted repository, one after another
+    Strict {
+        names: Vec<String>,
+        confirmed: usize,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Wrong,
+    // Strict confirmations only, the next name is due
+    Next,
+    Confirmed,
+}
+
+impl Confirmation {
+    // Large batches and batches with repositories others may rely on need every name typed
+    pub fn new(owner: &str, deleted: &[&Repository], threshold: usize, now: DateTime<Utc>) -> Self {
+        let recently_pushed = |repo: &&Repository| {
+            repo.metadata
+                .pushed_at
+                .is_some_and(|pushed_at| now - pushed_at < Duration::days(RECENT_PUSH_DAYS))
+        };
+        let reason = if deleted.len() > threshold {
+            Some(format!("More than {threshold} repositories"))
+        } else if deleted
+            .iter()
+            .any(|repo| repo.metadata.stargazers_count > 0)
+        {
+            Some(String::from("Some repositories have stars"))
+        } else if deleted.iter().any(|repo| repo.metadata.forks_count > 0) {
+            Some(String::from("Some repositories have forks"))
+        } else if deleted.iter().any(recently_pushed) {
+            Some(format!(
+                "Some repositories were pushed to in the last {RECENT_PUSH_DAYS} days"
+            ))
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Confirmation::Strict {
+                names: deleted
+                    .iter()
+                    .map(|repo| display_name(repo, owner))
+                    .collect(),
+                confirmed: 0,
+                reason,
+            },
+            None => Confirmation::Simple {
+                owner: owner.to_owned(),
+                count: deleted.len(),
+            },
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        match self {
+            Confirmation::Simple { owner, count } => {
+                format!("Type '{owner}' or {count} to delete {count} repositories")
+            }
+            Confirmation::Strict {
+                names,
+                confirmed,
+                reason,
+            } => format!(
+                "{reason}, type '{}' to confirm ({} of {})",
+                names[*confirmed],
+                confirmed + 1,
+                names.len()
+            ),
+        }
+    }
+
+    pub fn answer(&mut self, input: &str) -> Answer {
+        let input = input.trim();
+        match self {
+            Confirmation::Simple { owner, count } => {
+                if input == owner || input.parse() == Ok(*count) {
+                    Answer::Confirmed
+                } else {
+                    Answer::Wrong
+                }
+            }
+            Confirmation::Strict {
+                names, confirmed, ..
+            } => {
+                if input != names[*confirmed] {
+                    return Answer::Wrong;
+                }
+                *confirmed += 1;
+                if *confirmed == names.len() {
+                    Answer::Confirmed
+                } else {
+                    Answer::Next
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{RepositoryMetadata, Status};
+    use chrono::TimeZone;
+
+    fn repository(owner: &str, name: &str, metadata: RepositoryMetadata) -> Repository {
+        Repository {
+            name: name.to_owned(),
+            owner: owner.to_owned(),
+            status: Status::Unselected,
+            metadata,
+            matched_rule: None,
+        }
+    }
+
+    #[test]
+    fn test_simple_confirmation_accepts_owner_or_count() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let alpha = repository("octocat", "alpha", RepositoryMetadata::default());
+        let beta = repository("octocat", "beta", RepositoryMetadata::default());
+        let mut confirmation = Confirmation::new("octocat", &[&alpha, &beta], 10, now);
+
+        assert_eq!(confirmation.answer("octo"), Answer::Wrong);
+        assert_eq!(confirmation.answer("3"), Answer::Wrong);
+        assert_eq!(confirmation.answer("2"), Answer::Confirmed);
+        assert_eq!(confirmation.answer(" octocat "), Answer::Confirmed);
+    }
+
+    #[test]
+    fn test_strict_confirmation_needs_every_name() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let alpha = repository("octocat", "alpha", RepositoryMetadata::default());
+        let starred = repository(
+            "github",
+            "beta",
+            RepositoryMetadata {
+                stargazers_count: 1,
+                ..RepositoryMetadata::default()
+            },
+        );
+        let mut confirmation = Confirmation::new("octocat", &[&alpha, &starred], 10, now);
+        assert_eq!(
+            confirmation.prompt(),
+            "Some repositories have stars, type 'alpha' to confirm (1 of 2)"
+        );
+
+        assert_eq!(confirmation.answer("octocat"), Answer::Wrong);
+        assert_eq!(confirmation.answer("alpha"), Answer::Next);
+        assert_eq!(confirmation.answer("beta"), Answer::Wrong);
+        assert_eq!(confirmation.answer("github/beta"), Answer::Confirmed);
+    }
+
+    #[test]
+    fn test_strict_confirmation_above_threshold_or_recent_push() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let old = repository(
+            "octocat",
+            "old",
+            RepositoryMetadata {
+                pushed_at: Some(now - Duration::days(400)),
+                ..RepositoryMetadata::default()
+            },
+        );
+        let recent = repository(
+            "octocat",
+            "recent",
+            RepositoryMetadata {
+                pushed_at: Some(now - Duration::days(3)),
+                ..RepositoryMetadata::default()
+            },
+        );
+
+        let simple = Confirmation::new("octocat", &[&old], 1, now);
+        assert!(matches!(simple, Confirmation::Simple { count: 1, .. }));
+        let large = Confirmation::new("octocat", &[&old, &old], 1, now);
+        assert!(large.prompt().starts_with("More than 1 repositories"));
+        let pushed = Confirmation::new("octocat", &[&old, &recent], 10, now);
+        assert!(matches!(pushed, Confirmation::Strict { .. }));
+    }
+}
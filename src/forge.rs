@@ -0,0 +1,731 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::{
+    github::RepositoryClient,
+    gitlab::GitlabClient,
+    host::Host,
+    http::{RateLimit, header, quota},
+    ui::{Repository, RepositoryListing},
+};
+
+// Repositories of one page and the URL of the next one, if there is one
+pub type Page = (Vec<Repository>, Option<String>);
+
+// Sent along by GitHub with every response to a request authenticated with an expiring token
+pub const TOKEN_EXPIRATION: &str = "github-authentication-token-expiration";
+
+// Errors of the client have to cross task boundaries, so they need to be `Send`
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// The service hosting the repositories, chosen at startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    #[default]
+    Github,
+    Gitlab,
+}
+
+impl ForgeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForgeKind::Github => "GitHub",
+            ForgeKind::Gitlab => "GitLab",
+        }
+    }
+
+    pub fn default_host(&self) -> &'static str {
+        match self {
+            ForgeKind::Github => "github.com",
+            ForgeKind::Gitlab => "gitlab.com",
+        }
+    }
+
+    // Checked in this order, `GH_TOKEN` is what the GitHub CLI uses
+    pub fn token_variables(&self) -> &'static [&'static str] {
+        match self {
+            ForgeKind::Github => &["GITHUB_TOKEN", "GH_TOKEN"],
+            ForgeKind::Gitlab => &["GITLAB_TOKEN"],
+        }
+    }
+
+    // Listing private repositories needs `repo` and deleting them `delete_repo` on GitHub, GitLab
+    // has `api` for everything
+    pub fn required_scopes(&self) -> &'static [&'static str] {
+        match self {
+            ForgeKind::Github => &["repo", "delete_repo"],
+            ForgeKind::Gitlab => &["api"],
+        }
+    }
+
+    pub fn delete_scope(&self) -> &'static str {
+        match self {
+            ForgeKind::Github => "delete_repo",
+            ForgeKind::Gitlab => "api",
+        }
+    }
+
+    // Whether backups can export issues, pull requests and releases, see `Forge::get_all_items`
+    pub fn supports_exports(&self) -> bool {
+        *self == ForgeKind::Github
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    All,
+    Public,
+    Private,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affiliation {
+    Owner,
+    Collaborator,
+    OrganizationMember,
+}
+
+// Which repositories to list for the authenticated user, or for one of their organizations or
+// groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoQuery {
+    pub visibility: Visibility,
+    // Organization listings ignore these, they only know the visibility
+    pub affiliations: Vec<Affiliation>,
+    pub organization: Option<String>,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::All => "all",
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Visibility::All => Visibility::Public,
+            Visibility::Public => Visibility::Private,
+            Visibility::Private => Visibility::All,
+        }
+    }
+}
+
+impl Affiliation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Affiliation::Owner => "owner",
+            Affiliation::Collaborator => "collaborator",
+            Affiliation::OrganizationMember => "organization_member",
+        }
+    }
+}
+
+impl Default for RepoQuery {
+    fn default() -> Self {
+        Self {
+            visibility: Visibility::All,
+            affiliations: vec![Affiliation::Owner],
+            organization: None,
+        }
+    }
+}
+
+impl RepoQuery {
+    // Adds or removes an affiliation. GitHub needs at least one, so removing the last one is
+    // refused and `false` is returned.
+    pub fn toggle_affiliation(&mut self, affiliation: Affiliation) -> bool {
+        if let Some(i) = self.affiliations.iter().position(|a| *a == affiliation) {
+            if self.affiliations.len() == 1 {
+                return false;
+            }
+            self.affiliations.remove(i);
+        } else {
+            self.affiliations.push(affiliation);
+        }
+        true
+    }
+
+    pub fn affiliation_param(&self) -> String {
+        self.affiliations
+            .iter()
+            .map(Affiliation::as_str)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// Everything shown in the detail pane next to the repository list. It needs several requests,
+// so it is only fetched for the highlighted repository and cached afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct RepositoryDetails {
+    pub description: Option<String>,
+    pub topics: Vec<String>,
+    pub homepage: Option<String>,
+    pub open_issues: u32,
+    pub open_pull_requests: u32,
+    pub latest_commit: Option<CommitSummary>,
+    pub branches: u32,
+    pub releases: u32,
+    pub readme_excerpt: Option<String>,
+    pub is_template: bool,
+    // Full name of the repository this one was forked from
+    pub parent: Option<String>,
+    // Default branch of the parent, forks can be renamed away from it
+    pub parent_default_branch: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub message: String,
+    pub date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded,
+    Forbidden,
+    NotFound,
+    RateLimited,
+    NetworkError,
+    // Nothing was sent, the message tells what would have been
+    DryRun,
+    // Deletion was refused because the repository could not be backed up
+    BackupFailed,
+    // The repository matches a protected pattern, nothing was sent
+    Protected,
+    // Any other failure, the status code tells what happened
+    Failed,
+}
+
+// Result of a request changing a repository, with the forge's explanation if it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeResult {
+    pub outcome: Outcome,
+    // `None` if the request never got a response
+    pub status: Option<StatusCode>,
+    pub message: Option<String>,
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Succeeded => "done",
+            Outcome::Forbidden => "forbidden",
+            Outcome::NotFound => "not found",
+            Outcome::RateLimited => "rate limited",
+            Outcome::NetworkError => "network error",
+            Outcome::DryRun => "dry run",
+            Outcome::BackupFailed => "backup failed",
+            Outcome::Protected => "protected",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+impl ChangeResult {
+    pub async fn from_response(res: reqwest::Response, forge: ForgeKind) -> Self {
+        let status = res.status();
+        if status.is_success() {
+            return Self {
+                outcome: Outcome::Succeeded,
+                status: Some(status),
+                message: None,
+            };
+        }
+
+        let error = ForgeErrorKind::from_response(res).await;
+        let outcome = match &error {
+            ForgeErrorKind::RateLimited { .. } => Outcome::RateLimited,
+            ForgeErrorKind::NotFound { .. } => Outcome::NotFound,
+            _ if status == StatusCode::FORBIDDEN => Outcome::Forbidden,
+            _ if status == StatusCode::NOT_FOUND => Outcome::NotFound,
+            _ => Outcome::Failed,
+        };
+        Self {
+            outcome,
+            status: Some(status),
+            message: Some(error.on(forge).to_string()),
+        }
+    }
+
+    pub fn dry_run(request: String) -> Self {
+        Self {
+            outcome: Outcome::DryRun,
+            status: None,
+            message: Some(format!("would send {request}")),
+        }
+    }
+
+    pub fn network_error(error: &reqwest::Error) -> Self {
+        Self {
+            outcome: Outcome::NetworkError,
+            status: None,
+            message: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+    documentation_url: Option<String>,
+    // Details of validation errors, e.g. which field is invalid
+    #[serde(default)]
+    errors: Vec<ValidationError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidationError {
+    field: Option<String>,
+    code: Option<String>,
+    message: Option<String>,
+}
+
+// Why a request to the forge failed, see `ForgeError` for the messages
+#[derive(Debug)]
+pub enum ForgeErrorKind {
+    // 401, GitHub doesn't tell invalid, revoked and expired tokens apart unless the expiry is
+    // sent along
+    Unauthorized {
+        expired_at: Option<DateTime<Utc>>,
+    },
+    // 403 or 404 where GitHub named the scope or permission the token lacks
+    MissingScope {
+        message: String,
+        // E.g. "scope delete_repo" or "permission administration=write"
+        required: String,
+    },
+    NotFound {
+        message: String,
+    },
+    RateLimited {
+        message: String,
+        reset: Option<DateTime<Utc>>,
+    },
+    // Any other answer, e.g. 422 for invalid input
+    Api {
+        status: StatusCode,
+        message: String,
+        documentation_url: Option<String>,
+    },
+    // The forge could not be reached
+    Transport(reqwest::Error),
+    // The forge answered with something knife doesn't understand
+    Decode(String),
+    // The forge has no API for it, e.g. exports on GitLab
+    Unsupported(String),
+}
+
+impl ForgeErrorKind {
+    pub async fn from_response(res: Response) -> Self {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let response = res
+            .text()
+            .await
+            .ok()
+            .and_then(|body| serde_json::from_str::<ErrorResponse>(&body).ok());
+        let (message, documentation_url) = match response {
+            Some(response) => {
+                let mut message = response.message;
+                let details: Vec<String> = response
+                    .errors
+                    .into_iter()
+                    .filter_map(|error| {
+                        error.message.or(match (error.field, error.code) {
+                            (Some(field), Some(code)) => Some(format!("{field} is {code}")),
+                            _ => None,
+                        })
+                    })
+                    .collect();
+                if !details.is_empty() {
+                    message.push_str(&format!(": {}", details.join(", ")));
+                }
+                (message, response.documentation_url)
+            }
+            None => (
+                status
+                    .canonical_reason()
+                    .unwrap_or("Unknown error")
+                    .to_owned(),
+                None,
+            ),
+        };
+
+        if status == StatusCode::UNAUTHORIZED {
+            let expired_at = header(&headers, TOKEN_EXPIRATION)
+                .and_then(parse_expiration)
+                .filter(|expires_at| *expires_at <= Utc::now());
+            return ForgeErrorKind::Unauthorized { expired_at };
+        }
+        // Secondary rate limits come as 403 and are only recognizable by their message
+        let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+            || (status == StatusCode::FORBIDDEN
+                && (header(&headers, "x-ratelimit-remaining") == Some("0")
+                    || message.contains("rate limit")));
+        if rate_limited {
+            let reset = quota(&headers).map(|quota| quota.reset);
+            return ForgeErrorKind::RateLimited { message, reset };
+        }
+        // GitHub hides private repositories from tokens without access behind a 404
+        if (status == StatusCode::FORBIDDEN || status == StatusCode::NOT_FOUND)
+            && let Some(required) = required_access(&headers)
+        {
+            return ForgeErrorKind::MissingScope { message, required };
+        }
+        if status == StatusCode::NOT_FOUND {
+            return ForgeErrorKind::NotFound { message };
+        }
+        ForgeErrorKind::Api {
+            status,
+            message,
+            documentation_url,
+        }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ForgeErrorKind::Unauthorized { .. } => Some(StatusCode::UNAUTHORIZED),
+            ForgeErrorKind::NotFound { .. } => Some(StatusCode::NOT_FOUND),
+            ForgeErrorKind::Api { status, .. } => Some(*status),
+            ForgeErrorKind::Transport(e) => e.status(),
+            ForgeErrorKind::MissingScope { .. }
+            | ForgeErrorKind::RateLimited { .. }
+            | ForgeErrorKind::Decode(_)
+            | ForgeErrorKind::Unsupported(_) => None,
+        }
+    }
+
+    pub fn documentation_url(&self) -> Option<&str> {
+        match self {
+            ForgeErrorKind::Api {
+                documentation_url, ..
+            } => documentation_url.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn on(self, forge: ForgeKind) -> ForgeError {
+        ForgeError { forge, kind: self }
+    }
+}
+
+// A failed request along with the forge it went to, which the messages name
+#[derive(Debug)]
+pub struct ForgeError {
+    pub forge: ForgeKind,
+    pub kind: ForgeErrorKind,
+}
+
+impl ForgeError {
+    pub fn status(&self) -> Option<StatusCode> {
+        self.kind.status()
+    }
+
+    pub fn documentation_url(&self) -> Option<&str> {
+        self.kind.documentation_url()
+    }
+
+    // What the user can do about it
+    pub fn hint(&self) -> String {
+        let forge = self.forge.name();
+        match &self.kind {
+            ForgeErrorKind::Unauthorized { .. } => {
+                String::from("Create a new token and sign in with it.")
+            }
+            ForgeErrorKind::MissingScope { required, .. } => format!(
+                "Give the token the {required} in {forge}'s token settings, or sign in with another token."
+            ),
+            ForgeErrorKind::NotFound { .. } => String::from(
+                "The repository may have been deleted, renamed or transferred in the meantime.",
+            ),
+            ForgeErrorKind::RateLimited {
+                reset: Some(reset), ..
+            } => format!(
+                "Try again after {} UTC, or lower `concurrency` in the config.",
+                reset.format("%H:%M")
+            ),
+            ForgeErrorKind::RateLimited { reset: None, .. } => String::from(
+                "Wait a minute before trying again, or lower `concurrency` in the config.",
+            ),
+            ForgeErrorKind::Api {
+                documentation_url: Some(url),
+                ..
+            } => format!("See {url} for what {forge} expects."),
+            ForgeErrorKind::Api { .. } => format!("Try again, {forge} may have a hiccup."),
+            ForgeErrorKind::Transport(_) => String::from("Check your network connection."),
+            ForgeErrorKind::Decode(_) => {
+                format!("Check that knife talks to the {forge} API and not another server.")
+            }
+            ForgeErrorKind::Unsupported(_) => String::from("Turn it off in the config."),
+        }
+    }
+}
+
+impl std::fmt::Display for ForgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let forge = self.forge.name();
+        match &self.kind {
+            ForgeErrorKind::Unauthorized {
+                expired_at: Some(expired_at),
+            } => write!(f, "The token expired on {}", expired_at.format("%Y-%m-%d")),
+            ForgeErrorKind::Unauthorized { expired_at: None } => write!(
+                f,
+                "{forge} rejected the token, it is invalid, expired or was revoked"
+            ),
+            ForgeErrorKind::MissingScope { message, required } => {
+                write!(f, "{message} (the token needs the {required})")
+            }
+            ForgeErrorKind::NotFound { message }
+            | ForgeErrorKind::RateLimited { message, .. }
+            | ForgeErrorKind::Api { message, .. } => write!(f, "{message}"),
+            ForgeErrorKind::Transport(e) => write!(f, "Could not reach {forge}: {e}"),
+            ForgeErrorKind::Decode(e) => write!(f, "Unexpected answer from {forge}: {e}"),
+            ForgeErrorKind::Unsupported(what) => write!(f, "{what} not supported"),
+        }
+    }
+}
+
+impl std::error::Error for ForgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ForgeErrorKind::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ForgeErrorKind {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            ForgeErrorKind::Decode(error.to_string())
+        } else {
+            ForgeErrorKind::Transport(error)
+        }
+    }
+}
+
+impl From<serde_json::Error> for ForgeErrorKind {
+    fn from(error: serde_json::Error) -> Self {
+        ForgeErrorKind::Decode(error.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for ForgeErrorKind {
+    fn from(error: base64::DecodeError) -> Self {
+        ForgeErrorKind::Decode(error.to_string())
+    }
+}
+
+// Who the token belongs to and what it may do, read from the response to `/user`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    // The scopes needed differ per forge, see `ForgeKind::required_scopes`
+    pub forge: ForgeKind,
+    pub login: String,
+    // Only classic and OAuth tokens have scopes, fine-grained tokens have permissions per
+    // repository that can't be checked up front
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TokenInfo {
+    pub fn missing_scopes(&self) -> Vec<&'static str> {
+        let Some(scopes) = &self.scopes else {
+            return Vec::new();
+        };
+        self.forge
+            .required_scopes()
+            .iter()
+            .copied()
+            .filter(|required| !scopes.iter().any(|scope| scope == required))
+            .collect()
+    }
+
+    pub fn can_delete(&self) -> bool {
+        !self.missing_scopes().contains(&self.forge.delete_scope())
+    }
+
+    // E.g. "octocat · scopes: repo, delete_repo · expires 2024-07-01"
+    pub fn describe(&self) -> String {
+        let mut text = self.login.clone();
+        match &self.scopes {
+            Some(scopes) if scopes.is_empty() => text.push_str(" · no scopes"),
+            Some(scopes) => text.push_str(&format!(" · scopes: {}", scopes.join(", "))),
+            None if self.forge == ForgeKind::Github => text.push_str(" · fine-grained token"),
+            None => text.push_str(" · unknown scopes"),
+        }
+        match self.expires_at {
+            Some(expires_at) => {
+                text.push_str(&format!(" · expires {}", expires_at.format("%Y-%m-%d")))
+            }
+            None => text.push_str(" · never expires"),
+        }
+        text
+    }
+}
+
+// E.g. "2024-07-01 12:00:00 UTC" or "2024-07-01 12:00:00 -0700"
+pub fn parse_expiration(value: &str) -> Option<DateTime<Utc>> {
+    let value = match value.strip_suffix(" UTC") {
+        Some(value) => format!("{value} +0000"),
+        None => value.to_owned(),
+    };
+    DateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S %z")
+        .ok()
+        .map(|expires_at| expires_at.with_timezone(&Utc))
+}
+
+// What GitHub says the token would have needed, from the headers of a failed request
+fn required_access(headers: &HeaderMap) -> Option<String> {
+    let value = |name: &str| {
+        header(headers, name)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(permissions) = value("x-accepted-github-permissions") {
+        return Some(format!("permission {permissions}"));
+    }
+    let accepted = value("x-accepted-oauth-scopes")?;
+    let granted: Vec<&str> = value("x-oauth-scopes")
+        .map(|scopes| scopes.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    let missing = !accepted
+        .split(',')
+        .map(str::trim)
+        .any(|scope| granted.contains(&scope));
+    missing.then(|| format!("scope {accepted}"))
+}
+
+// What knife needs from a forge to list and change repositories. Details, upstream comparisons
+// and exports only exist on GitHub, the defaults stand in for forges without them.
+pub trait Forge: Send + Sync {
+    fn kind(&self) -> ForgeKind;
+
+    // Backups clone with it
+    fn token(&self) -> &str;
+
+    // Changes are only described, nothing is sent or backed up
+    fn dry_run(&self) -> bool;
+
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit::default()
+    }
+
+    // The owner of the token and what it may do
+    fn check_token(&self) -> BoxFuture<'_, Result<TokenInfo, ForgeError>>;
+
+    fn get_organizations(&self) -> BoxFuture<'_, Result<Vec<String>, ForgeError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    // The first page of repositories, `RepositoryListing::next_page` points to the next one
+    fn get_repos<'a>(
+        &'a self,
+        owner: &'a str,
+        query: &'a RepoQuery,
+    ) -> BoxFuture<'a, Result<RepositoryListing, ForgeError>>;
+
+    fn get_repos_page<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Page, ForgeError>>;
+
+    // `None` if the forge has no details to show
+    fn get_repo_details<'a>(
+        &'a self,
+        _owner: &'a str,
+        _repo: &'a str,
+    ) -> BoxFuture<'a, Result<Option<RepositoryDetails>, ForgeError>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn cached_repo_details(&self, _owner: &str, _repo: &str) -> Option<RepositoryDetails> {
+        None
+    }
+
+    // `None` if the repository is not a fork or can't be compared with its parent
+    fn commits_ahead_of_parent<'a>(
+        &'a self,
+        _owner: &'a str,
+        _repo: &'a str,
+        _branch: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u32>, ForgeError>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    // Every item of a list endpoint of the repository, exported by backups. Configs asking for
+    // exports on forges without them are rejected at startup, see `Config::validate`.
+    fn get_all_items<'a>(
+        &'a self,
+        _owner: &'a str,
+        _repo: &'a str,
+        _endpoint: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Value>, ForgeError>> {
+        let error = format!("Exports on {} are", self.kind().name());
+        let forge = self.kind();
+        Box::pin(async move { Err(ForgeErrorKind::Unsupported(error).on(forge)) })
+    }
+
+    fn delete_repo<'a>(&'a self, owner: &'a str, repo: &'a str) -> BoxFuture<'a, ChangeResult>;
+
+    fn set_archived<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        archived: bool,
+    ) -> BoxFuture<'a, ChangeResult>;
+
+    fn set_private<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        private: bool,
+    ) -> BoxFuture<'a, ChangeResult>;
+}
+
+// The client for the forge of the host, shared by all background tasks
+pub fn connect(host: &Host, token: &str, dry_run: bool) -> Arc<dyn Forge> {
+    match host.forge {
+        ForgeKind::Github => Arc::new(RepositoryClient::new(token, host).with_dry_run(dry_run)),
+        ForgeKind::Gitlab => Arc::new(GitlabClient::new(token, host).with_dry_run(dry_run)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_affiliation_keeps_at_least_one() {
+        let mut query = RepoQuery::default();
+        assert!(!query.toggle_affiliation(Affiliation::Owner));
+        assert_eq!(query.affiliation_param(), "owner");
+
+        assert!(query.toggle_affiliation(Affiliation::Collaborator));
+        assert!(query.toggle_affiliation(Affiliation::Owner));
+        assert_eq!(query.affiliation_param(), "collaborator");
+    }
+    #[test]
+    fn test_errors_name_the_forge() {
+        let error = ForgeErrorKind::Api {
+            status: StatusCode::BAD_GATEWAY,
+            message: String::from("Bad Gateway"),
+            documentation_url: None,
+        }
+        .on(ForgeKind::Gitlab);
+        assert_eq!(error.hint(), "Try again, GitLab may have a hiccup.");
+        let error = ForgeErrorKind::Unauthorized { expired_at: None }.on(ForgeKind::Gitlab);
+        assert_eq!(
+            error.to_string(),
+            "GitLab rejected the token, it is invalid, expired or was revoked"
+        );
+    }
+}
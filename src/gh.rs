@@ -0,0 +1,135 @@
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+
+use crate::forge::BoxError;
+
+// A login of the GitHub CLI, as stored by `gh auth login`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhLogin {
+    pub host: String,
+    pub user: Option<String>,
+    pub token: String,
+}
+
+// One host in `hosts.yml`. Older versions of gh only have `user` and `oauth_token`, newer ones
+// also list every account below `users`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Host {
+    user: Option<String>,
+    oauth_token: Option<String>,
+    users: HashMap<String, Option<User>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct User {
+    oauth_token: Option<String>,
+}
+
+// Where gh keeps its config: `$GH_CONFIG_DIR`, then `$XDG_CONFIG_HOME/gh`, then `~/.config/gh`
+pub fn hosts_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("GH_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?
+            .join("gh"),
+    };
+    Some(config_dir.join("hosts.yml"))
+}
+
+// The gh login for the host, or `None` if gh isn't logged in there
+pub fn load(host: &str) -> Result<Option<GhLogin>, BoxError> {
+    let Some(path) = hosts_path() else {
+        return Ok(None);
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => parse(&content, host).map_err(|e| {
+            format!("Could not read the gh login from {}: {e}", path.display()).into()
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Could not read {}: {e}", path.display()).into()),
+    }
+}
+
+fn parse(content: &str, host: &str) -> Result<Option<GhLogin>, BoxError> {
+    let mut hosts: HashMap<String, Host> = serde_yaml_ng::from_str(content)?;
+    let Some(entry) = hosts.remove(host) else {
+        return Ok(None);
+    };
+    let token = entry.oauth_token.or_else(|| {
+        let user = entry.user.as_ref()?;
+        entry.users.get(user)?.as_ref()?.oauth_token.clone()
+    });
+    match token.filter(|token| !token.is_empty()) {
+        Some(token) => Ok(Some(GhLogin {
+            host: host.to_owned(),
+            user: entry.user,
+            token,
+        })),
+        // Since gh 2.40 tokens go to the system keyring unless `--insecure-storage` is used
+        None => Err(format!(
+            "gh keeps the token for {host} in the system keyring, \
+             run `gh auth token | knife --token-file -` instead"
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const DEFAULT_HOST: &str = "github.com";
+
+    #[test]
+    fn test_parse_legacy_and_multi_account_hosts() {
+        let legacy = indoc! {"
+            github.com:
+                user: octocat
+                oauth_token: gho_legacy
+                git_protocol: https
+        "};
+        assert_eq!(
+            parse(legacy, DEFAULT_HOST).unwrap(),
+            Some(GhLogin {
+                host: String::from("github.com"),
+                user: Some(String::from("octocat")),
+                token: String::from("gho_legacy"),
+            })
+        );
+
+        let accounts = indoc! {"
+            github.com:
+                git_protocol: ssh
+                users:
+                    hubot:
+                    octocat:
+                        oauth_token: gho_octocat
+                user: octocat
+            github.example.com:
+                user: monalisa
+                oauth_token: gho_enterprise
+        "};
+        let login = parse(accounts, DEFAULT_HOST).unwrap().unwrap();
+        assert_eq!(login.token, "gho_octocat");
+        let login = parse(accounts, "github.example.com").unwrap().unwrap();
+        assert_eq!(login.user.as_deref(), Some("monalisa"));
+        assert_eq!(parse(accounts, "gitlab.com").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_token_in_keyring() {
+        let keyring = indoc! {"
+            github.com:
+                users:
+                    octocat:
+                user: octocat
+        "};
+        let error = parse(keyring, DEFAULT_HOST).unwrap_err();
+        assert!(error.to_string().contains("gh auth token"));
+        assert!(parse("github.com: [", DEFAULT_HOST).is_err());
+    }
+}
@@ -1,23 +1,124 @@
-use crate::ui::{GithubContent, Repository, Status};
-use ratatui::widgets::ListState;
-use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use crate::forge::{
+    ChangeResult, CommitSummary, Forge, ForgeError, ForgeErrorKind, ForgeKind, Page, RepoQuery,
+    RepositoryDetails, TOKEN_EXPIRATION, TokenInfo, parse_expiration,
+};
+use crate::host::Host;
+use crate::http::{HttpClient, RateLimit, header, link_url};
+use crate::ui::{Repository, RepositoryListing, RepositoryMetadata, Status};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+// GitHub allows at most 100 items per page, the default is 30.
+const PER_PAGE: u8 = 100;
+// Number of README lines shown in the detail pane
+const README_EXCERPT_LINES: usize = 8;
+
+#[derive(Debug, Deserialize)]
+struct RepositoryResponse {
+    name: String,
+    owner: OwnerResponse,
+    #[serde(flatten)]
+    metadata: RepositoryMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnerResponse {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryDetailsResponse {
+    description: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+    homepage: Option<String>,
+    // GitHub counts pull requests as issues as well
+    #[serde(default)]
+    open_issues_count: u32,
+    #[serde(default)]
+    is_template: bool,
+    parent: Option<ParentResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParentResponse {
+    full_name: String,
+    default_branch: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitResponse {
+    commit: CommitInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitInfo {
+    message: String,
+    committer: Option<CommitAuthor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitAuthor {
+    date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompareResponse {
+    ahead_by: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadmeResponse {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    login: String,
+}
+
+// Cheap to clone, clones share the connection pool and the details cache so they can be handed
+// to background tasks
+#[derive(Clone)]
 pub struct RepositoryClient {
-    client: Client,
+    http: HttpClient,
     token: String,
+    base_url: String,
+    // Details per full repository name, see `get_repo_details`
+    details_cache: Arc<Mutex<HashMap<String, RepositoryDetails>>>,
+    dry_run: bool,
 }
 
 impl RepositoryClient {
-    pub fn new(token: &str) -> Self {
+    pub fn new(token: &str, host: &Host) -> Self {
+        Self::with_base_url(token, &host.api_url)
+    }
+
+    pub fn with_base_url(token: &str, base_url: &str) -> Self {
         Self {
-            client: Client::new(),
+            http: HttpClient::default(),
             token: token.to_owned(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            details_cache: Arc::new(Mutex::new(HashMap::new())),
+            dry_run: false,
         }
     }
 
+    // A dry run client reads as usual but only reports the requests that would change something
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
     fn build_request(&self, method: reqwest::Method, uri: &str) -> RequestBuilder {
-        self.client
+        self.http
             .request(method, uri)
             .header("Accept", "application/vnd.github+json")
             .header("Authorization", format!("Bearer {}", self.token))
@@ -25,69 +126,1056 @@ impl RepositoryClient {
             .header("User-Agent", "knife")
     }
 
-    pub async fn get_owner(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-        const USER_URL: &str = "https://api.github.com/user";
-        let res = self.build_request(Method::GET, USER_URL).send().await?;
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn rate_limit(&self) -> RateLimit {
+        self.http.rate_limit()
+    }
+
+    // Reads the owner of the token along with its scopes and expiry, so a token that won't work
+    // is noticed before anything is selected
+    pub async fn check_token(&self) -> Result<TokenInfo, ForgeErrorKind> {
+        let url = format!("{}/user", self.base_url);
+        let res = self
+            .http
+            .send(self.build_request(Method::GET, &url))
+            .await?;
         if !res.status().is_success() {
-            let error_msg = format!(
-                "Could not get owner from GitHub. Request failed with status code: {}",
-                res.status()
-            );
-            return Err(error_msg.into());
+            return Err(ForgeErrorKind::from_response(res).await);
         }
+        let headers = res.headers();
+        let expires_at = header(headers, TOKEN_EXPIRATION).and_then(parse_expiration);
+        let scopes = header(headers, "x-oauth-scopes").map(|scopes| {
+            scopes
+                .split(',')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_owned)
+                .collect()
+        });
         let body = res.text().await?;
-        let value: Value = serde_json::from_str(&body)?;
-        let owner = value
-            .get("login")
-            .and_then(Value::as_str)
-            .ok_or("Primary key not found")?;
+        let user: UserResponse = serde_json::from_str(&body)?;
+
+        Ok(TokenInfo {
+            forge: ForgeKind::Github,
+            login: user.login,
+            scopes,
+            expires_at,
+        })
+    }
+
+    // Logins of the organizations the user is a member of, offered next to their own account
+    pub async fn get_organizations(&self) -> Result<Vec<String>, ForgeErrorKind> {
+        let mut next_page = Some(format!("{}/user/orgs?per_page={PER_PAGE}", self.base_url));
+        let mut organizations = Vec::new();
+        while let Some(url) = next_page {
+            let res = self
+                .http
+                .send(self.build_request(Method::GET, &url))
+                .await?;
+            if !res.status().is_success() {
+                return Err(ForgeErrorKind::from_response(res).await);
+            }
+            next_page = link_url(res.headers(), "next");
+            let body = res.text().await?;
+            let page: Vec<OwnerResponse> = serde_json::from_str(&body)?;
+            organizations.extend(page.into_iter().map(|organization| organization.login));
+        }
 
-        Ok(owner.to_owned())
+        Ok(organizations)
     }
 
+    // Fetches the first page of repositories. Further pages are loaded with `get_repos_page` so
+    // the list can be shown while the rest is still arriving.
     pub async fn get_repos(
-        &mut self,
+        &self,
         owner: &str,
-    ) -> Result<GithubContent, Box<dyn std::error::Error>> {
-        let url = format!("https://api.github.com/users/{owner}/repos");
-        let res = self.build_request(Method::GET, &url).send().await?;
+        query: &RepoQuery,
+    ) -> Result<RepositoryListing, ForgeErrorKind> {
+        let url = match &query.organization {
+            Some(organization) => format!(
+                "{}/orgs/{organization}/repos?per_page={PER_PAGE}&type={}",
+                self.base_url,
+                query.visibility.as_str()
+            ),
+            None => format!(
+                "{}/user/repos?per_page={PER_PAGE}&visibility={}&affiliation={}",
+                self.base_url,
+                query.visibility.as_str(),
+                query.affiliation_param()
+            ),
+        };
+        let (repos, next_page) = self.get_repos_page(&url).await?;
+
+        Ok(RepositoryListing::new(
+            owner,
+            repos,
+            next_page,
+            query.clone(),
+        ))
+    }
+
+    // Fetches one page of repositories and the URL of the page after it
+    pub async fn get_repos_page(
+        &self,
+        url: &str,
+    ) -> Result<(Vec<Repository>, Option<String>), ForgeErrorKind> {
+        let res = self.http.send(self.build_request(Method::GET, url)).await?;
         if !res.status().is_success() {
-            let error_msg = format!(
-                "Could not get repositories from GitHub. Request failed with status code: {}",
-                res.status()
-            );
-            return Err(error_msg.into());
+            return Err(ForgeErrorKind::from_response(res).await);
         }
 
+        let next_page = link_url(res.headers(), "next");
         let body = res.text().await?;
-
-        let value: Value = serde_json::from_str(&body)?;
-
-        let repos = value
-            .as_array()
-            .ok_or("Expected an array")?
-            .iter()
-            .filter_map(|item| item.get("name").and_then(Value::as_str))
-            .map(|s| Repository {
-                name: s.to_owned(),
+        let repos: Vec<RepositoryResponse> = serde_json::from_str(&body)?;
+        let repos = repos
+            .into_iter()
+            .map(|repo| Repository {
+                name: repo.name,
+                owner: repo.owner.login,
                 status: Status::Unselected,
+                metadata: repo.metadata,
+                matched_rule: None,
             })
             .collect::<Vec<_>>();
 
-        Ok(GithubContent {
-            repos,
-            owner: owner.to_owned(),
-            list_state: ListState::default(),
-        })
+        Ok((repos, next_page))
     }
 
-    pub async fn delete_repo(
+    // Returns the details of a repository, fetching them on first access only
+    pub async fn get_repo_details(
         &self,
         owner: &str,
         repo: &str,
-    ) -> Result<StatusCode, Box<dyn std::error::Error>> {
-        let url = format!("https://api.github.com/repos/{owner}/{repo}");
-        let res = self.build_request(Method::DELETE, &url).send().await?;
-        Ok(res.status())
+    ) -> Result<RepositoryDetails, ForgeErrorKind> {
+        let full_name = format!("{owner}/{repo}");
+        if let Some(details) = self.cached_repo_details(owner, repo) {
+            return Ok(details);
+        }
+
+        let repo_url = format!("{}/repos/{full_name}", self.base_url);
+        let pulls_url = format!("{repo_url}/pulls?state=open&per_page=1");
+        let branches_url = format!("{repo_url}/branches?per_page=1");
+        let releases_url = format!("{repo_url}/releases?per_page=1");
+        let (response, open_pull_requests, branches, releases, latest_commit, readme_excerpt) = tokio::join!(
+            self.get_json::<RepositoryDetailsResponse>(&repo_url),
+            self.count_items(&pulls_url),
+            self.count_items(&branches_url),
+            self.count_items(&releases_url),
+            self.get_latest_commit(&repo_url),
+            self.get_readme_excerpt(&repo_url),
+        );
+        let response = response?;
+        let open_pull_requests = open_pull_requests?;
+
+        let details = RepositoryDetails {
+            description: response.description,
+            topics: response.topics,
+            homepage: response.homepage.filter(|homepage| !homepage.is_empty()),
+            open_issues: response
+                .open_issues_count
+                .saturating_sub(open_pull_requests),
+            open_pull_requests,
+            latest_commit: latest_commit?,
+            branches: branches?,
+            releases: releases?,
+            readme_excerpt: readme_excerpt?,
+            is_template: response.is_template,
+            parent_default_branch: response
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.default_branch.clone()),
+            parent: response.parent.map(|parent| parent.full_name),
+        };
+        self.details_cache
+            .lock()
+            .unwrap()
+            .insert(full_name, details.clone());
+
+        Ok(details)
+    }
+
+    pub fn cached_repo_details(&self, owner: &str, repo: &str) -> Option<RepositoryDetails> {
+        self.details_cache
+            .lock()
+            .unwrap()
+            .get(&format!("{owner}/{repo}"))
+            .cloned()
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, ForgeErrorKind> {
+        let res = self.http.send(self.build_request(Method::GET, url)).await?;
+        if !res.status().is_success() {
+            return Err(ForgeErrorKind::from_response(res).await);
+        }
+        let body = res.text().await?;
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    // Collects every page of a list endpoint of the repository, e.g. `issues?state=all`
+    pub async fn get_all_items(
+        &self,
+        owner: &str,
+        repo: &str,
+        endpoint: &str,
+    ) -> Result<Vec<Value>, ForgeErrorKind> {
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        let mut next_page = Some(format!(
+            "{}/repos/{owner}/{repo}/{endpoint}{separator}per_page={PER_PAGE}",
+            self.base_url
+        ));
+        let mut items = Vec::new();
+        while let Some(url) = next_page {
+            let res = self
+                .http
+                .send(self.build_request(Method::GET, &url))
+                .await?;
+            if !res.status().is_success() {
+                return Err(ForgeErrorKind::from_response(res).await);
+            }
+            next_page = link_url(res.headers(), "next");
+            let body = res.text().await?;
+            items.extend(serde_json::from_str::<Vec<Value>>(&body)?);
+        }
+
+        Ok(items)
+    }
+
+    // Counts the items of a list endpoint requested with `per_page=1`: the `last` link then
+    // points to the page number that equals the total.
+    async fn count_items(&self, url: &str) -> Result<u32, ForgeErrorKind> {
+        let res = self.http.send(self.build_request(Method::GET, url)).await?;
+        if !res.status().is_success() {
+            return Err(ForgeErrorKind::from_response(res).await);
+        }
+        if let Some(last) = link_url(res.headers(), "last").and_then(|url| page_number(&url)) {
+            return Ok(last);
+        }
+        let body = res.text().await?;
+        let items: Vec<Value> = serde_json::from_str(&body)?;
+
+        Ok(items.len() as u32)
+    }
+
+    async fn get_latest_commit(
+        &self,
+        repo_url: &str,
+    ) -> Result<Option<CommitSummary>, ForgeErrorKind> {
+        let url = format!("{repo_url}/commits?per_page=1");
+        let res = self
+            .http
+            .send(self.build_request(Method::GET, &url))
+            .await?;
+        // Empty repositories answer with 409 Conflict
+        if res.status() == StatusCode::CONFLICT {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            return Err(ForgeErrorKind::from_response(res).await);
+        }
+        let body = res.text().await?;
+        let commits: Vec<CommitResponse> = serde_json::from_str(&body)?;
+
+        Ok(commits.into_iter().next().map(|commit| CommitSummary {
+            message: commit.commit.message,
+            date: commit.commit.committer.and_then(|committer| committer.date),
+        }))
+    }
+
+    async fn get_readme_excerpt(&self, repo_url: &str) -> Result<Option<String>, ForgeErrorKind> {
+        let url = format!("{repo_url}/readme");
+        let res = self
+            .http
+            .send(self.build_request(Method::GET, &url))
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !res.status().is_success() {
+            return Err(ForgeErrorKind::from_response(res).await);
+        }
+        let body = res.text().await?;
+        let readme: ReadmeResponse = serde_json::from_str(&body)?;
+        // The content is base64 encoded and wrapped with newlines
+        let encoded: String = readme.content.split_whitespace().collect();
+        let decoded = STANDARD.decode(encoded)?;
+
+        Ok(Some(readme_excerpt(&String::from_utf8_lossy(&decoded))))
+    }
+
+    // Number of commits the default branch of a fork has that the default branch of its parent
+    // doesn't. `None` if the repository is not a fork.
+    pub async fn commits_ahead_of_parent(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<Option<u32>, ForgeErrorKind> {
+        let details = self.get_repo_details(owner, repo).await?;
+        let Some(parent) = details.parent else {
+            return Ok(None);
+        };
+        let parent_owner = parent.split('/').next().unwrap_or_default();
+        let parent_branch = details.parent_default_branch.as_deref().unwrap_or(branch);
+        let url = format!(
+            "{}/repos/{owner}/{repo}/compare/{parent_owner}:{parent_branch}...{owner}:{branch}",
+            self.base_url
+        );
+        let compare: CompareResponse = self.get_json(&url).await?;
+
+        Ok(Some(compare.ahead_by))
+    }
+
+    pub async fn delete_repo(&self, owner: &str, repo: &str) -> ChangeResult {
+        if self.dry_run {
+            return ChangeResult::dry_run(format!("DELETE /repos/{owner}/{repo}"));
+        }
+        let url = format!("{}/repos/{owner}/{repo}", self.base_url);
+        match self
+            .http
+            .send(self.build_request(Method::DELETE, &url))
+            .await
+        {
+            Ok(res) => ChangeResult::from_response(res, ForgeKind::Github).await,
+            Err(e) => ChangeResult::network_error(&e),
+        }
+    }
+
+    // Archived repositories become read-only, unarchiving makes them writable again
+    pub async fn set_archived(&self, owner: &str, repo: &str, archived: bool) -> ChangeResult {
+        self.update_repo(owner, repo, serde_json::json!({ "archived": archived }))
+            .await
+    }
+
+    pub async fn set_private(&self, owner: &str, repo: &str, private: bool) -> ChangeResult {
+        self.update_repo(owner, repo, serde_json::json!({ "private": private }))
+            .await
+    }
+
+    async fn update_repo(&self, owner: &str, repo: &str, body: serde_json::Value) -> ChangeResult {
+        if self.dry_run {
+            return ChangeResult::dry_run(format!("PATCH /repos/{owner}/{repo} {body}"));
+        }
+        let url = format!("{}/repos/{owner}/{repo}", self.base_url);
+        let request = self
+            .build_request(Method::PATCH, &url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        match self.http.send(request).await {
+            Ok(res) => ChangeResult::from_response(res, ForgeKind::Github).await,
+            Err(e) => ChangeResult::network_error(&e),
+        }
+    }
+}
+
+impl Forge for RepositoryClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Github
+    }
+
+    fn token(&self) -> &str {
+        RepositoryClient::token(self)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        RepositoryClient::rate_limit(self)
+    }
+
+    fn check_token(&self) -> BoxFuture<'_, Result<TokenInfo, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::check_token(self)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn get_organizations(&self) -> BoxFuture<'_, Result<Vec<String>, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::get_organizations(self)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn get_repos<'a>(
+        &'a self,
+        owner: &'a str,
+        query: &'a RepoQuery,
+    ) -> BoxFuture<'a, Result<RepositoryListing, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::get_repos(self, owner, query)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn get_repos_page<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Page, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::get_repos_page(self, url)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn get_repo_details<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+    ) -> BoxFuture<'a, Result<Option<RepositoryDetails>, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::get_repo_details(self, owner, repo)
+                .await
+                .map(Some)
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn cached_repo_details(&self, owner: &str, repo: &str) -> Option<RepositoryDetails> {
+        RepositoryClient::cached_repo_details(self, owner, repo)
+    }
+
+    fn commits_ahead_of_parent<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        branch: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u32>, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::commits_ahead_of_parent(self, owner, repo, branch)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn get_all_items<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        endpoint: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Value>, ForgeError>> {
+        Box::pin(async move {
+            RepositoryClient::get_all_items(self, owner, repo, endpoint)
+                .await
+                .map_err(|e| e.on(ForgeKind::Github))
+        })
+    }
+
+    fn delete_repo<'a>(&'a self, owner: &'a str, repo: &'a str) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(RepositoryClient::delete_repo(self, owner, repo))
+    }
+
+    fn set_archived<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        archived: bool,
+    ) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(RepositoryClient::set_archived(self, owner, repo, archived))
+    }
+
+    fn set_private<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        private: bool,
+    ) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(RepositoryClient::set_private(self, owner, repo, private))
+    }
+}
+
+// Reads the `page` query parameter of a pagination link
+fn page_number(url: &str) -> Option<u32> {
+    Url::parse(url)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == "page")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn readme_excerpt(readme: &str) -> String {
+    readme
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .take(README_EXCERPT_LINES)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::{Affiliation, Outcome, Visibility};
+    use crate::http::Quota;
+    use chrono::TimeZone;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_get_repos_follows_next_links() {
+        let server = MockServer::start().await;
+        let first_page: Vec<_> = (0..100)
+            .map(|i| json!({ "name": format!("repo-{i}"), "owner": { "login": "octocat" } }))
+            .collect();
+        let second_page = vec![json!({ "name": "repo-100", "owner": { "login": "octocat" } })];
+
+        Mock::given(method("GET"))
+            .and(path("/user/repos"))
+            .and(query_param("per_page", "100"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(second_page))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user/repos"))
+            .and(query_param("per_page", "100"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{}/user/repos?per_page=100&page=2>; rel="next""#,
+                            server.uri()
+                        ),
+                    )
+                    .set_body_json(first_page),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let listing = client
+            .get_repos("octocat", &RepoQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(listing.repos.len(), 100);
+
+        let next_page = listing.next_page.unwrap();
+        let (repos, next_page) = client.get_repos_page(&next_page).await.unwrap();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].name, "repo-100");
+        assert!(next_page.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_repos_sends_visibility_and_affiliation() {
+        let server = MockServer::start().await;
+        let repos = vec![
+            json!({
+                "name": "secret",
+                "owner": { "login": "octocat" },
+                "pushed_at": "2021-03-04T10:00:00Z",
+                "stargazers_count": 2,
+                "size": 120,
+                "private": true,
+                "language": "Rust",
+                "default_branch": "main"
+            }),
+            json!({ "name": "shared", "owner": { "login": "acme" } }),
+        ];
+
+        Mock::given(method("GET"))
+            .and(path("/user/repos"))
+            .and(query_param("visibility", "private"))
+            .and(query_param("affiliation", "owner,organization_member"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(repos))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut query = RepoQuery {
+            visibility: Visibility::Private,
+            ..RepoQuery::default()
+        };
+        assert!(query.toggle_affiliation(Affiliation::OrganizationMember));
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let listing = client.get_repos("octocat", &query).await.unwrap();
+        assert_eq!(listing.repos.len(), 2);
+        assert_eq!(listing.repos[1].owner, "acme");
+
+        let metadata = &listing.repos[0].metadata;
+        assert_eq!(
+            metadata.pushed_at.map(|date| date.to_rfc3339()).as_deref(),
+            Some("2021-03-04T10:00:00+00:00")
+        );
+        assert_eq!(metadata.stargazers_count, 2);
+        assert_eq!(metadata.language.as_deref(), Some("Rust"));
+        assert!(metadata.private && !metadata.fork);
+        assert_eq!(listing.repos[1].metadata.pushed_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_organization_repos() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user/orgs"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([{ "login": "acme" }, { "login": "initech" }])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/orgs/acme/repos"))
+            .and(query_param("type", "private"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {
+                    "name": "experiment",
+                    "owner": { "login": "acme" },
+                    "permissions": { "admin": false, "push": true, "pull": true }
+                },
+                {
+                    "name": "prototype",
+                    "owner": { "login": "acme" },
+                    "permissions": { "admin": true, "push": true, "pull": true }
+                }
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let organizations = client.get_organizations().await.unwrap();
+        assert_eq!(organizations, vec!["acme", "initech"]);
+
+        let query = RepoQuery {
+            visibility: Visibility::Private,
+            organization: Some(String::from("acme")),
+            ..RepoQuery::default()
+        };
+        let listing = client.get_repos("acme", &query).await.unwrap();
+        assert_eq!(listing.owner, "acme");
+        assert!(!listing.repos[0].metadata.can_administer());
+        assert!(listing.repos[1].metadata.can_administer());
+    }
+
+    #[tokio::test]
+    async fn test_get_repo_details_is_fetched_once() {
+        let server = MockServer::start().await;
+        let repo_path = "/repos/octocat/hello";
+
+        Mock::given(method("GET"))
+            .and(path(repo_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "description": "Says hello",
+                "topics": ["greeting"],
+                "homepage": "",
+                "open_issues_count": 5,
+                "is_template": false,
+                "parent": { "full_name": "upstream/hello", "default_branch": "master" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{repo_path}/pulls")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{0}{repo_path}/pulls?per_page=1&page=2>; rel="next", <{0}{repo_path}/pulls?per_page=1&page=2>; rel="last""#,
+                            server.uri()
+                        ),
+                    )
+                    .set_body_json(json!([{}])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{repo_path}/branches")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{}])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{repo_path}/releases")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{repo_path}/commits")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "commit": {
+                    "message": "Initial commit",
+                    "committer": { "date": "2020-01-01T00:00:00Z" }
+                }
+            }])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{repo_path}/readme")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "content": STANDARD.encode("# hello\n\nA friendly repo\n")
+            })))
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let details = client.get_repo_details("octocat", "hello").await.unwrap();
+        assert_eq!(details.open_pull_requests, 2);
+        assert_eq!(details.open_issues, 3);
+        assert_eq!(details.branches, 1);
+        assert_eq!(details.releases, 0);
+        assert_eq!(details.homepage, None);
+        assert_eq!(details.parent.as_deref(), Some("upstream/hello"));
+        assert_eq!(
+            details
+                .latest_commit
+                .map(|commit| commit.message)
+                .as_deref(),
+            Some("Initial commit")
+        );
+        assert_eq!(
+            details.readme_excerpt.as_deref(),
+            Some("# hello\nA friendly repo")
+        );
+
+        // The second lookup is served from the cache, the mock expects a single request
+        client.get_repo_details("octocat", "hello").await.unwrap();
+        assert!(client.cached_repo_details("octocat", "hello").is_some());
+
+        // The fork is compared with the parent's default branch, not a branch of the same name
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "{repo_path}/compare/upstream:master...octocat:main"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ahead_by": 2 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let ahead = client
+            .commits_ahead_of_parent("octocat", "hello", "main")
+            .await;
+        assert_eq!(ahead.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_check_token_reads_scopes_and_expiry() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("X-OAuth-Scopes", "repo, read:org")
+                    .insert_header(TOKEN_EXPIRATION, "2030-07-01 12:00:00 -0700")
+                    .set_body_json(json!({ "login": "octocat" })),
+            )
+            .mount(&server)
+            .await;
+
+        // The way an Enterprise Server with its API elsewhere is configured
+        let host = Host::new(ForgeKind::Github, "github.example.com", Some(&server.uri()));
+        let client = RepositoryClient::new("token", &host);
+        let info = client.check_token().await.unwrap();
+        assert_eq!(info.login, "octocat");
+        assert_eq!(info.missing_scopes(), vec!["delete_repo"]);
+        assert!(!info.can_delete());
+        assert_eq!(
+            info.describe(),
+            "octocat · scopes: repo, read:org · expires 2030-07-01"
+        );
+
+        // Fine-grained tokens come without scopes
+        let fine_grained = TokenInfo {
+            scopes: None,
+            expires_at: None,
+            ..info
+        };
+        assert!(fine_grained.can_delete());
+        assert_eq!(
+            fine_grained.describe(),
+            "octocat · fine-grained token · never expires"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_token_tells_invalid_and_expired_apart() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .insert_header(TOKEN_EXPIRATION, "2020-01-31 08:00:00 UTC")
+                    .set_body_json(json!({ "message": "Bad credentials" })),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(
+                ResponseTemplate::new(401).set_body_json(json!({ "message": "Bad credentials" })),
+            )
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let expired = client.check_token().await.unwrap_err();
+        assert!(matches!(
+            expired,
+            ForgeErrorKind::Unauthorized { expired_at: Some(expired_at) }
+                if expired_at == Utc.with_ymd_and_hms(2020, 1, 31, 8, 0, 0).unwrap()
+        ));
+        assert_eq!(
+            expired.on(ForgeKind::Github).to_string(),
+            "The token expired on 2020-01-31"
+        );
+        assert!(matches!(
+            client.check_token().await.unwrap_err(),
+            ForgeErrorKind::Unauthorized { expired_at: None }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_change_names_missing_scope() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("X-OAuth-Scopes", "repo")
+                    .insert_header("X-Accepted-OAuth-Scopes", "delete_repo")
+                    .set_body_json(json!({ "message": "Must have admin rights to Repository." })),
+            )
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let result = client.delete_repo("octocat", "hello").await;
+        assert_eq!(result.outcome, Outcome::Forbidden);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("Must have admin rights to Repository. (the token needs the scope delete_repo)")
+        );
+    }
+
+    // Retries without waiting seconds between attempts
+    fn fast_client(server: &MockServer) -> RepositoryClient {
+        RepositoryClient {
+            http: HttpClient::without_delays(),
+            ..RepositoryClient::with_base_url("token", &server.uri())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_server_errors_and_secondary_rate_limits() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "0")
+                    .set_body_json(
+                        json!({ "message": "You have exceeded a secondary rate limit." }),
+                    ),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-limit", "5000")
+                    .insert_header("x-ratelimit-remaining", "4998")
+                    .insert_header("x-ratelimit-reset", "1717243200")
+                    .set_body_json(json!({ "name": "hello" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = fast_client(&server);
+        let url = format!("{}/repos/octocat/hello", server.uri());
+        let repo = client.get_json::<Value>(&url).await.unwrap();
+        assert_eq!(repo["name"], "hello");
+        assert_eq!(
+            client.rate_limit(),
+            RateLimit {
+                quota: Some(Quota {
+                    limit: 5000,
+                    remaining: 4998,
+                    reset: Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap(),
+                }),
+                waiting_until: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_changes_only_when_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/limited"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // The first attempt may have deleted the repository, a second one would say it's gone
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/hello"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_json(json!({ "message": "Not Found" })),
+            )
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let client = fast_client(&server);
+        let limited = client.delete_repo("octocat", "limited").await;
+        assert_eq!(limited.outcome, Outcome::Succeeded);
+        let result = client.delete_repo("octocat", "hello").await;
+        assert_eq!(result.outcome, Outcome::Failed);
+        assert_eq!(result.status, Some(StatusCode::BAD_GATEWAY));
+    }
+
+    #[tokio::test]
+    async fn test_send_gives_up_on_long_rate_limits_and_repeated_errors() {
+        let server = MockServer::start().await;
+        let reset = (Utc::now().timestamp() + 3600).to_string();
+        Mock::given(method("DELETE"))
+            .and(path("/repos/octocat/limited"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-limit", "5000")
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("x-ratelimit-reset", reset.as_str())
+                    .set_body_json(json!({ "message": "API rate limit exceeded" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octocat/broken"))
+            .respond_with(ResponseTemplate::new(500))
+            // The first attempt and four retries
+            .expect(5)
+            .mount(&server)
+            .await;
+
+        let client = fast_client(&server);
+        let limited = client.delete_repo("octocat", "limited").await;
+        assert_eq!(limited.outcome, Outcome::RateLimited);
+        assert_eq!(client.rate_limit().quota.unwrap().remaining, 0);
+        let url = format!("{}/repos/octocat/broken", server.uri());
+        let broken = client.get_json::<Value>(&url).await.unwrap_err();
+        assert!(matches!(
+            broken,
+            ForgeErrorKind::Api { status, .. } if status == StatusCode::INTERNAL_SERVER_ERROR
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_classified() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/invalid"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+                "message": "Validation Failed",
+                "errors": [{ "resource": "Repository", "field": "name", "code": "invalid" }],
+                "documentation_url": "https://docs.github.com/rest/repos/repos#update-a-repository"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/hidden"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .insert_header("X-OAuth-Scopes", "public_repo")
+                    .insert_header("X-Accepted-OAuth-Scopes", "repo")
+                    .set_body_json(json!({ "message": "Not Found" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/gone"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_json(json!({ "message": "Not Found" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/garbage"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>"))
+            .mount(&server)
+            .await;
+
+        let client = RepositoryClient::with_base_url("token", &server.uri());
+        let error = |path: &'static str| {
+            let client = client.clone();
+            let url = format!("{}{path}", server.uri());
+            async move { client.get_json::<Value>(&url).await.unwrap_err() }
+        };
+
+        let invalid = error("/invalid").await;
+        assert!(matches!(
+            invalid,
+            ForgeErrorKind::Api { status, .. } if status == StatusCode::UNPROCESSABLE_ENTITY
+        ));
+        let invalid = invalid.on(ForgeKind::Github);
+        assert_eq!(invalid.to_string(), "Validation Failed: name is invalid");
+        assert_eq!(
+            invalid.documentation_url(),
+            Some("https://docs.github.com/rest/repos/repos#update-a-repository")
+        );
+        assert_eq!(
+            error("/hidden").await.on(ForgeKind::Github).to_string(),
+            "Not Found (the token needs the scope repo)"
+        );
+        assert!(matches!(
+            error("/gone").await,
+            ForgeErrorKind::NotFound { .. }
+        ));
+        assert!(matches!(error("/garbage").await, ForgeErrorKind::Decode(_)));
+        let unreachable = RepositoryClient {
+            base_url: String::from("http://127.0.0.1:9"),
+            ..fast_client(&server)
+        };
+        let transport = unreachable
+            .get_json::<Value>("http://127.0.0.1:9/user")
+            .await
+            .unwrap_err();
+        assert!(matches!(transport, ForgeErrorKind::Transport(_)));
+        let transport = transport.on(ForgeKind::Github);
+        assert!(
+            transport
+                .to_string()
+                .starts_with("Could not reach GitHub: ")
+        );
+        assert_eq!(transport.hint(), "Check your network connection.");
     }
 }
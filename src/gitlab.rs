@@ -0,0 +1,559 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use futures::future::BoxFuture;
+use reqwest::{Method, RequestBuilder};
+use serde::{Deserialize, de::IgnoredAny};
+
+use crate::{
+    forge::{
+        Affiliation, ChangeResult, Forge, ForgeError, ForgeErrorKind, ForgeKind, Page, RepoQuery,
+        TokenInfo, Visibility,
+    },
+    host::Host,
+    http::{HttpClient, RateLimit, link_url},
+    ui::{Permissions, Repository, RepositoryListing, RepositoryMetadata, Status},
+};
+
+// GitLab allows at most 100 items per page, the default is 20.
+const PER_PAGE: u8 = 100;
+// Only owners may delete projects, see https://docs.gitlab.com/user/permissions/
+const OWNER_ACCESS_LEVEL: u8 = 50;
+
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    username: String,
+}
+
+// Only answered for personal access tokens, OAuth tokens get a 404
+#[derive(Debug, Deserialize)]
+struct PersonalAccessTokenResponse {
+    #[serde(default)]
+    scopes: Vec<String>,
+    expires_at: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupResponse {
+    full_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectResponse {
+    // The name in URLs, `name` may contain spaces
+    path: String,
+    namespace: NamespaceResponse,
+    description: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+    default_branch: Option<String>,
+    #[serde(default)]
+    http_url_to_repo: String,
+    #[serde(default)]
+    visibility: String,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    star_count: u32,
+    #[serde(default)]
+    forks_count: u32,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    // GitLab has no push date, any activity in the project counts
+    last_activity_at: Option<DateTime<Utc>>,
+    forked_from_project: Option<IgnoredAny>,
+    // Only sent with `statistics=true` to members with at least the Reporter role
+    statistics: Option<StatisticsResponse>,
+    permissions: Option<PermissionsResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamespaceResponse {
+    // E.g. "group/subgroup" for projects of nested groups
+    full_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatisticsResponse {
+    // In bytes
+    repository_size: u64,
+}
+
+// The user's access through the project itself and through its group, the higher one counts
+#[derive(Debug, Deserialize)]
+struct PermissionsResponse {
+    project_access: Option<AccessResponse>,
+    group_access: Option<AccessResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessResponse {
+    access_level: u8,
+}
+
+impl ProjectResponse {
+    fn into_repository(self) -> Repository {
+        let permissions = self.permissions.map(|permissions| {
+            let access_level = [permissions.project_access, permissions.group_access]
+                .into_iter()
+                .flatten()
+                .map(|access| access.access_level)
+                .max()
+                .unwrap_or_default();
+            Permissions {
+                admin: access_level >= OWNER_ACCESS_LEVEL,
+            }
+        });
+        Repository {
+            name: self.path,
+            owner: self.namespace.full_path,
+            status: Status::Unselected,
+            metadata: RepositoryMetadata {
+                pushed_at: self.last_activity_at,
+                updated_at: self.updated_at,
+                created_at: self.created_at,
+                stargazers_count: self.star_count,
+                forks_count: self.forks_count,
+                size: self
+                    .statistics
+                    .map(|statistics| statistics.repository_size / 1024)
+                    .unwrap_or_default(),
+                fork: self.forked_from_project.is_some(),
+                archived: self.archived,
+                // Internal projects are visible to every signed in user, so they count as public
+                private: self.visibility == "private",
+                language: None,
+                description: self
+                    .description
+                    .filter(|description| !description.is_empty()),
+                topics: self.topics,
+                default_branch: self.default_branch.unwrap_or_default(),
+                clone_url: self.http_url_to_repo,
+                permissions,
+            },
+            matched_rule: None,
+        }
+    }
+}
+
+// Talks to the REST API of gitlab.com or a self-managed GitLab. Failures are reported as
+// `ForgeErrorKind`, GitLab answers with the same status codes and a `message` as well.
+#[derive(Clone)]
+pub struct GitlabClient {
+    http: HttpClient,
+    token: String,
+    base_url: String,
+    dry_run: bool,
+}
+
+impl GitlabClient {
+    pub fn new(token: &str, host: &Host) -> Self {
+        Self::with_base_url(token, &host.api_url)
+    }
+
+    pub fn with_base_url(token: &str, base_url: &str) -> Self {
+        Self {
+            http: HttpClient::default(),
+            token: token.to_owned(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            dry_run: false,
+        }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    fn build_request(&self, method: Method, uri: &str) -> RequestBuilder {
+        self.http
+            .request(method, uri)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("User-Agent", "knife")
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<(T, Option<String>), ForgeErrorKind> {
+        let res = self.http.send(self.build_request(Method::GET, url)).await?;
+        if !res.status().is_success() {
+            return Err(ForgeErrorKind::from_response(res).await);
+        }
+        let next_page = link_url(res.headers(), "next");
+        let body = res.text().await?;
+
+        Ok((serde_json::from_str(&body)?, next_page))
+    }
+
+    // Projects are addressed by their URL encoded full path, e.g. `group%2Fsubgroup%2Fproject`
+    fn project_url(&self, owner: &str, repo: &str) -> String {
+        let id = format!("{owner}/{repo}").replace('/', "%2F");
+        format!("{}/projects/{id}", self.base_url)
+    }
+
+    pub async fn check_token(&self) -> Result<TokenInfo, ForgeErrorKind> {
+        let (user, _) = self
+            .get_json::<UserResponse>(&format!("{}/user", self.base_url))
+            .await?;
+        let token = self
+            .get_json::<PersonalAccessTokenResponse>(&format!(
+                "{}/personal_access_tokens/self",
+                self.base_url
+            ))
+            .await
+            .ok()
+            .map(|(token, _)| token);
+
+        Ok(TokenInfo {
+            forge: ForgeKind::Gitlab,
+            login: user.username,
+            scopes: token.as_ref().map(|token| token.scopes.clone()),
+            // Tokens expire at the start of the day
+            expires_at: token
+                .and_then(|token| token.expires_at)
+                .map(|date| date.and_time(NaiveTime::MIN).and_utc()),
+        })
+    }
+
+    // Groups the user owns, offered like GitHub organizations
+    pub async fn get_organizations(&self) -> Result<Vec<String>, ForgeErrorKind> {
+        let mut next_page = Some(format!(
+            "{}/groups?per_page={PER_PAGE}&min_access_level={OWNER_ACCESS_LEVEL}",
+            self.base_url
+        ));
+        let mut groups = Vec::new();
+        while let Some(url) = next_page {
+            let (page, next) = self.get_json::<Vec<GroupResponse>>(&url).await?;
+            groups.extend(page.into_iter().map(|group| group.full_path));
+            next_page = next;
+        }
+
+        Ok(groups)
+    }
+
+    // Owned projects, or all the user is a member of once collaborations or organizations are
+    // asked for. GitLab can't tell those two apart.
+    pub async fn get_repos(
+        &self,
+        owner: &str,
+        query: &RepoQuery,
+    ) -> Result<RepositoryListing, ForgeErrorKind> {
+        let mut url = match &query.organization {
+            Some(group) => format!(
+                "{}/groups/{}/projects?include_subgroups=true",
+                self.base_url,
+                group.replace('/', "%2F")
+            ),
+            None if query.affiliations == [Affiliation::Owner] => {
+                format!("{}/projects?owned=true", self.base_url)
+            }
+            None => format!("{}/projects?membership=true", self.base_url),
+        };
+        url.push_str(&format!("&per_page={PER_PAGE}&statistics=true"));
+        if query.visibility != Visibility::All {
+            url.push_str(&format!("&visibility={}", query.visibility.as_str()));
+        }
+        let (repos, next_page) = self.get_repos_page(&url).await?;
+
+        Ok(RepositoryListing::new(
+            owner,
+            repos,
+            next_page,
+            query.clone(),
+        ))
+    }
+
+    pub async fn get_repos_page(
+        &self,
+        url: &str,
+    ) -> Result<(Vec<Repository>, Option<String>), ForgeErrorKind> {
+        let (projects, next_page) = self.get_json::<Vec<ProjectResponse>>(url).await?;
+        let repos = projects
+            .into_iter()
+            .map(ProjectResponse::into_repository)
+            .collect();
+
+        Ok((repos, next_page))
+    }
+
+    // GitLab answers 202, projects may only be marked for deletion depending on the instance
+    pub async fn delete_repo(&self, owner: &str, repo: &str) -> ChangeResult {
+        let url = self.project_url(owner, repo);
+        self.change(Method::DELETE, &url, None).await
+    }
+
+    pub async fn set_archived(&self, owner: &str, repo: &str, archived: bool) -> ChangeResult {
+        let action = if archived { "archive" } else { "unarchive" };
+        let url = format!("{}/{action}", self.project_url(owner, repo));
+        self.change(Method::POST, &url, None).await
+    }
+
+    pub async fn set_private(&self, owner: &str, repo: &str, private: bool) -> ChangeResult {
+        let visibility = if private { "private" } else { "public" };
+        let url = self.project_url(owner, repo);
+        let body = serde_json::json!({ "visibility": visibility });
+        self.change(Method::PUT, &url, Some(body)).await
+    }
+
+    async fn change(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<serde_json::Value>,
+    ) -> ChangeResult {
+        if self.dry_run {
+            let path = url.strip_prefix(&self.base_url).unwrap_or(url);
+            let request = match &body {
+                Some(body) => format!("{method} {path} {body}"),
+                None => format!("{method} {path}"),
+            };
+            return ChangeResult::dry_run(request);
+        }
+        let mut request = self.build_request(method, url);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        match self.http.send(request).await {
+            Ok(res) => ChangeResult::from_response(res, ForgeKind::Gitlab).await,
+            Err(e) => ChangeResult::network_error(&e),
+        }
+    }
+}
+
+impl Forge for GitlabClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Gitlab
+    }
+
+    fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.http.rate_limit()
+    }
+
+    fn check_token(&self) -> BoxFuture<'_, Result<TokenInfo, ForgeError>> {
+        Box::pin(async move {
+            GitlabClient::check_token(self)
+                .await
+                .map_err(|e| e.on(ForgeKind::Gitlab))
+        })
+    }
+
+    fn get_organizations(&self) -> BoxFuture<'_, Result<Vec<String>, ForgeError>> {
+        Box::pin(async move {
+            GitlabClient::get_organizations(self)
+                .await
+                .map_err(|e| e.on(ForgeKind::Gitlab))
+        })
+    }
+
+    fn get_repos<'a>(
+        &'a self,
+        owner: &'a str,
+        query: &'a RepoQuery,
+    ) -> BoxFuture<'a, Result<RepositoryListing, ForgeError>> {
+        Box::pin(async move {
+            GitlabClient::get_repos(self, owner, query)
+                .await
+                .map_err(|e| e.on(ForgeKind::Gitlab))
+        })
+    }
+
+    fn get_repos_page<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Page, ForgeError>> {
+        Box::pin(async move {
+            GitlabClient::get_repos_page(self, url)
+                .await
+                .map_err(|e| e.on(ForgeKind::Gitlab))
+        })
+    }
+
+    fn delete_repo<'a>(&'a self, owner: &'a str, repo: &'a str) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(GitlabClient::delete_repo(self, owner, repo))
+    }
+
+    fn set_archived<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        archived: bool,
+    ) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(GitlabClient::set_archived(self, owner, repo, archived))
+    }
+
+    fn set_private<'a>(
+        &'a self,
+        owner: &'a str,
+        repo: &'a str,
+        private: bool,
+    ) -> BoxFuture<'a, ChangeResult> {
+        Box::pin(GitlabClient::set_private(self, owner, repo, private))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::Outcome;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_check_token_and_list_projects() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .and(header("Authorization", "Bearer glpat-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "username": "tanuki" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/personal_access_tokens/self"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "scopes": ["read_api"],
+                "expires_at": "2030-07-01"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/projects"))
+            .and(query_param("owned", "true"))
+            .and(query_param("visibility", "private"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {
+                    "path": "old-experiment",
+                    "namespace": { "full_path": "tanuki" },
+                    "description": "",
+                    "topics": ["demo"],
+                    "default_branch": "main",
+                    "http_url_to_repo": "https://gitlab.com/tanuki/old-experiment.git",
+                    "visibility": "private",
+                    "star_count": 3,
+                    "last_activity_at": "2021-03-04T10:00:00Z",
+                    "forked_from_project": { "id": 1 },
+                    "statistics": { "repository_size": 204800 },
+                    "permissions": {
+                        "project_access": { "access_level": 30 },
+                        "group_access": { "access_level": 50 }
+                    }
+                }
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = GitlabClient::with_base_url("glpat-token", &server.uri());
+        let info = client.check_token().await.unwrap();
+        assert_eq!(info.login, "tanuki");
+        assert_eq!(info.missing_scopes(), vec!["api"]);
+        assert!(!info.can_delete());
+        assert_eq!(
+            info.describe(),
+            "tanuki · scopes: read_api · expires 2030-07-01"
+        );
+
+        let query = RepoQuery {
+            visibility: Visibility::Private,
+            ..RepoQuery::default()
+        };
+        let forge: &dyn Forge = &client;
+        let listing = forge.get_repos("tanuki", &query).await.unwrap();
+        let repo = &listing.repos[0];
+        assert_eq!(
+            (repo.owner.as_str(), repo.name.as_str()),
+            ("tanuki", "old-experiment")
+        );
+        let metadata = &repo.metadata;
+        assert!(metadata.private && metadata.fork && metadata.can_administer());
+        assert_eq!(metadata.size, 200);
+        assert_eq!(metadata.description, None);
+        assert_eq!(
+            metadata.pushed_at.map(|date| date.to_rfc3339()).as_deref(),
+            Some("2021-03-04T10:00:00+00:00")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changes_address_projects_by_path() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/projects/group%2Fsub%2Fold"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/projects/tanuki%2Fdone/archive"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/projects/tanuki%2Fforeign"))
+            .respond_with(
+                ResponseTemplate::new(403).set_body_json(json!({ "message": "403 Forbidden" })),
+            )
+            .mount(&server)
+            .await;
+
+        let client = GitlabClient::with_base_url("glpat-token", &server.uri());
+        let forge: &dyn Forge = &client;
+        let deleted = forge.delete_repo("group/sub", "old").await;
+        assert_eq!(deleted.outcome, Outcome::Succeeded);
+        let archived = forge.set_archived("tanuki", "done", true).await;
+        assert_eq!(archived.outcome, Outcome::Succeeded);
+        let forbidden = forge.delete_repo("tanuki", "foreign").await;
+        assert_eq!(forbidden.outcome, Outcome::Forbidden);
+        assert_eq!(forbidden.message.as_deref(), Some("403 Forbidden"));
+
+        let dry_run = client.with_dry_run(true);
+        let result = dry_run.set_private("tanuki", "done", true).await;
+        assert_eq!(result.outcome, Outcome::DryRun);
+        assert_eq!(
+            result.message.as_deref(),
+            Some(r#"would send PUT /projects/tanuki%2Fdone {"visibility":"private"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_are_waited_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/projects/tanuki%2Fold"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/projects/tanuki%2Fold"))
+            .respond_with(
+                ResponseTemplate::new(202)
+                    .insert_header("RateLimit-Limit", "2000")
+                    .insert_header("RateLimit-Remaining", "1999")
+                    .insert_header("RateLimit-Reset", "1717243200"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = GitlabClient {
+            http: HttpClient::without_delays(),
+            ..GitlabClient::with_base_url("glpat-token", &server.uri())
+        };
+        let forge: &dyn Forge = &client;
+        let deleted = forge.delete_repo("tanuki", "old").await;
+        assert_eq!(deleted.outcome, Outcome::Succeeded);
+        assert_eq!(
+            forge.rate_limit().quota.map(|quota| quota.remaining),
+            Some(1999)
+        );
+    }
+}
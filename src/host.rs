@@ -0,0 +1,94 @@
+use crate::forge::ForgeKind;
+
+// Where the forge lives, e.g. github.com, a GitHub Enterprise Server or a GitLab instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub forge: ForgeKind,
+    // E.g. "github.example.com", as gh names hosts in hosts.yml
+    pub name: String,
+    // E.g. "https://github.example.com", for the token page and the OAuth device flow
+    pub web_url: String,
+    pub api_url: String,
+}
+
+impl Host {
+    // The host may be given with or without scheme. Without an API URL it is derived the way the
+    // forge does it: `api.github.com` for github.com, `/api/v3` on Enterprise Server and
+    // `/api/v4` on GitLab.
+    pub fn new(forge: ForgeKind, host: &str, api_url: Option<&str>) -> Self {
+        let host = host.trim().trim_end_matches('/');
+        let (scheme, name) = match host.split_once("://") {
+            Some((scheme, name)) => (scheme, name),
+            None => ("https", host),
+        };
+        let web_url = format!("{scheme}://{name}");
+        let api_url = match (api_url, forge) {
+            (Some(api_url), _) => api_url.trim().trim_end_matches('/').to_owned(),
+            (None, ForgeKind::Github) if name == forge.default_host() => {
+                String::from("https://api.github.com")
+            }
+            (None, ForgeKind::Github) => format!("{web_url}/api/v3"),
+            (None, ForgeKind::Gitlab) => format!("{web_url}/api/v4"),
+        };
+        Self {
+            forge,
+            name: name.to_owned(),
+            web_url,
+            api_url,
+        }
+    }
+
+    // The page to create a token with the scopes knife needs, a classic one on GitHub
+    pub fn new_token_url(&self) -> String {
+        match self.forge {
+            ForgeKind::Github => format!(
+                "{}/settings/tokens/new?scopes=delete_repo,repo&description=Repo%20Remover%20Token",
+                self.web_url
+            ),
+            ForgeKind::Gitlab => format!(
+                "{}/-/user_settings/personal_access_tokens?name=knife&scopes=api",
+                self.web_url
+            ),
+        }
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        let forge = ForgeKind::default();
+        Self::new(forge, forge.default_host(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_url_is_derived_from_host() {
+        let github = Host::default();
+        assert_eq!(github.name, "github.com");
+        assert_eq!(github.api_url, "https://api.github.com");
+        assert_eq!(
+            github.new_token_url(),
+            "https://github.com/settings/tokens/new?scopes=delete_repo,repo&description=Repo%20Remover%20Token"
+        );
+
+        let enterprise = Host::new(ForgeKind::Github, "https://github.example.com/", None);
+        assert_eq!(enterprise.name, "github.example.com");
+        assert_eq!(enterprise.web_url, "https://github.example.com");
+        assert_eq!(enterprise.api_url, "https://github.example.com/api/v3");
+
+        let local = Host::new(
+            ForgeKind::Github,
+            "github.example.com",
+            Some("http://127.0.0.1:8080/"),
+        );
+        assert_eq!(local.web_url, "https://github.example.com");
+        assert_eq!(local.api_url, "http://127.0.0.1:8080");
+
+        let gitlab = Host::new(ForgeKind::Gitlab, "gitlab.com", None);
+        assert_eq!(gitlab.api_url, "https://gitlab.com/api/v4");
+        assert!(gitlab.new_token_url().ends_with("scopes=api"));
+    }
+}
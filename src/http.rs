@@ -0,0 +1,307 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_retries: 4,
+    base_delay: Duration::from_secs(1),
+    max_wait: Duration::from_secs(15 * 60),
+};
+// GitHub asks to wait at least a minute after a secondary rate limit without `Retry-After`
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+// How the client deals with transient failures and rate limits
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_retries: u32,
+    // Doubled after every failed attempt, half of it is random
+    base_delay: Duration,
+    // Rate limits that reset later than this are reported instead of waited out
+    max_wait: Duration,
+}
+
+// The primary rate limit as of the last response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub quota: Option<Quota>,
+    // Set while a request waits for a rate limit to reset
+    pub waiting_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    Backoff(Duration),
+    RateLimited(Duration),
+}
+
+// Sends the requests of a forge client. Cheap to clone, clones share the connection pool and the
+// rate limit, they count against the same quota.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    rate_limit: Arc<Mutex<RateLimit>>,
+    retry: RetryPolicy,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            client: Client::new(),
+            rate_limit: Arc::new(Mutex::new(RateLimit::default())),
+            retry: RETRY_POLICY,
+        }
+    }
+}
+
+impl HttpClient {
+    // Retries without waiting seconds between attempts
+    #[cfg(test)]
+    pub fn without_delays() -> Self {
+        Self {
+            retry: RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..RETRY_POLICY
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub fn rate_limit(&self) -> RateLimit {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    // Sends the request, retrying connection errors with backoff and waiting out rate limits.
+    // Reads are also retried after timeouts and 5xx responses, changes aren't as they may have
+    // been made anyway. The last response is returned as is once retrying doesn't help.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            // Bodies are strings, so requests can always be cloned
+            let Some(current) = request.try_clone() else {
+                return request.send().await;
+            };
+            let (client, current) = current.build_split();
+            let current = current?;
+            let method = current.method().clone();
+            let retry = match client.execute(current).await {
+                Ok(res) => {
+                    if let Some(quota) = quota(res.headers()) {
+                        self.rate_limit.lock().unwrap().quota = Some(quota);
+                    }
+                    let retry = retry_after(
+                        &method,
+                        res.status(),
+                        res.headers(),
+                        attempt,
+                        &self.retry,
+                        Utc::now(),
+                    );
+                    match retry {
+                        Some(Retry::RateLimited(wait)) if wait > self.retry.max_wait => {
+                            return Ok(res);
+                        }
+                        Some(retry) if attempt < self.retry.max_retries => retry,
+                        _ => return Ok(res),
+                    }
+                }
+                Err(e)
+                    if attempt < self.retry.max_retries
+                        && (e.is_connect() || (e.is_timeout() && method == Method::GET)) =>
+                {
+                    Retry::Backoff(backoff(attempt, &self.retry))
+                }
+                Err(e) => return Err(e),
+            };
+
+            match retry {
+                Retry::Backoff(delay) => tokio::time::sleep(delay).await,
+                Retry::RateLimited(wait) => {
+                    let until = Utc::now() + wait;
+                    self.rate_limit.lock().unwrap().waiting_until = Some(until);
+                    tokio::time::sleep(wait).await;
+                    // Other requests of the batch may wait longer
+                    let mut rate_limit = self.rate_limit.lock().unwrap();
+                    if rate_limit.waiting_until == Some(until) {
+                        rate_limit.waiting_until = None;
+                    }
+                }
+            }
+            attempt += 1;
+        }
+    }
+}
+
+pub fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+// E.g. `remaining` reads `x-ratelimit-remaining` on GitHub and `ratelimit-remaining` on GitLab
+pub fn rate_limit_header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    header(headers, &format!("x-ratelimit-{name}"))
+        .or_else(|| header(headers, &format!("ratelimit-{name}")))
+}
+
+pub fn quota(headers: &HeaderMap) -> Option<Quota> {
+    let number = |name| rate_limit_header(headers, name)?.parse::<u32>().ok();
+    Some(Quota {
+        limit: number("limit")?,
+        remaining: number("remaining")?,
+        reset: DateTime::from_timestamp(rate_limit_header(headers, "reset")?.parse().ok()?, 0)?,
+    })
+}
+
+// How long to wait before sending the request again, if at all. A 5xx may come after the change
+// was made, so only reads are sent again then. See
+// https://docs.github.com/en/rest/using-the-rest-api/best-practices-for-using-the-rest-api#handle-rate-limit-errors-appropriately
+fn retry_after(
+    method: &Method,
+    status: StatusCode,
+    headers: &HeaderMap,
+    attempt: u32,
+    policy: &RetryPolicy,
+    now: DateTime<Utc>,
+) -> Option<Retry> {
+    if status.is_server_error() {
+        return (*method == Method::GET).then(|| Retry::Backoff(backoff(attempt, policy)));
+    }
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    // Secondary rate limits usually say how long to wait
+    if let Some(seconds) = header(headers, "retry-after").and_then(|value| value.parse().ok()) {
+        return Some(Retry::RateLimited(Duration::from_secs(seconds)));
+    }
+    if rate_limit_header(headers, "remaining") == Some("0") {
+        let reset = rate_limit_header(headers, "reset")?.parse().ok()?;
+        let wait = DateTime::from_timestamp(reset, 0)? - now;
+        // The reset is a whole second, wait for the next one to be safe
+        let wait = wait.to_std().unwrap_or_default() + Duration::from_secs(1);
+        return Some(Retry::RateLimited(wait));
+    }
+    // A 403 without rate limit headers is a missing permission
+    (status == StatusCode::TOO_MANY_REQUESTS)
+        .then_some(Retry::RateLimited(SECONDARY_RATE_LIMIT_WAIT))
+}
+
+// Exponential backoff with jitter, so parallel requests of a batch don't retry in lockstep
+fn backoff(attempt: u32, policy: &RetryPolicy) -> Duration {
+    let delay = policy.base_delay * 2u32.pow(attempt.min(16));
+    let half = delay / 2;
+    half + half.mul_f64(fastrand::f64())
+}
+
+// Extracts the target of a relation from a `Link` header such as
+// `<https://api.github.com/user/repos?page=2>; rel="next", <...>; rel="last"`.
+pub fn link_url(headers: &HeaderMap, rel: &str) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    let relation = format!(r#"rel="{rel}""#);
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        let matches = params.split(';').any(|param| param.trim() == relation);
+        matches.then(|| {
+            url.trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_owned()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_link_url_picks_relation() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            r#"<https://api.github.com/user/repos?page=2>; rel="next", <https://api.github.com/user/repos?page=5>; rel="last""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            link_url(&headers, "next").as_deref(),
+            Some("https://api.github.com/user/repos?page=2")
+        );
+        assert_eq!(
+            link_url(&headers, "last").as_deref(),
+            Some("https://api.github.com/user/repos?page=5")
+        );
+    }
+
+    #[test]
+    fn test_link_url_is_none_without_relation() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            r#"<https://api.github.com/user/repos?page=1>; rel="first", <https://api.github.com/user/repos?page=4>; rel="prev""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(link_url(&headers, "next"), None);
+        assert_eq!(link_url(&HeaderMap::new(), "next"), None);
+    }
+
+    #[test]
+    fn test_retry_after_rate_limits_and_server_errors() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let headers = |pairs: &[(&'static str, String)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        let retry = |status, headers: &HeaderMap| {
+            retry_after(&Method::DELETE, status, headers, 0, &RETRY_POLICY, now)
+        };
+
+        let secondary = headers(&[("retry-after", String::from("30"))]);
+        assert_eq!(
+            retry(StatusCode::FORBIDDEN, &secondary),
+            Some(Retry::RateLimited(Duration::from_secs(30)))
+        );
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", String::from("0")),
+            ("x-ratelimit-reset", (now.timestamp() + 120).to_string()),
+        ]);
+        assert_eq!(
+            retry(StatusCode::FORBIDDEN, &exhausted),
+            Some(Retry::RateLimited(Duration::from_secs(121)))
+        );
+        assert_eq!(
+            retry(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new()),
+            Some(Retry::RateLimited(SECONDARY_RATE_LIMIT_WAIT))
+        );
+        assert_eq!(retry(StatusCode::FORBIDDEN, &HeaderMap::new()), None);
+        assert_eq!(retry(StatusCode::NOT_FOUND, &HeaderMap::new()), None);
+        assert_eq!(retry(StatusCode::BAD_GATEWAY, &HeaderMap::new()), None);
+
+        let Some(Retry::Backoff(delay)) = retry_after(
+            &Method::GET,
+            StatusCode::BAD_GATEWAY,
+            &HeaderMap::new(),
+            2,
+            &RETRY_POLICY,
+            now,
+        ) else {
+            panic!("expected a backoff");
+        };
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+    }
+}
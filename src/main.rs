@@ -1,21 +1,78 @@
-use crate::app::App;
+use crate::{app::App, cli::Cli, config::Config};
+use clap::Parser;
 use ratatui::DefaultTerminal;
+use std::process::ExitCode;
 
 mod app;
+mod backup;
+mod batch;
+mod cli;
+mod config;
+mod confirm;
+mod forge;
+mod gh;
 mod github;
+mod gitlab;
+mod host;
+mod http;
+mod oauth;
+mod protected;
+mod rules;
+mod search;
+mod task;
+#[cfg(test)]
+mod test_support;
+mod token;
 mod ui;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    // Read the config before taking over the terminal, so errors in it stay readable
+    let mut config = config::load()?;
+    config.dry_run = cli.dry_run;
+    // Flags and their environment variables win over the config file
+    if let Some(forge) = cli.forge {
+        config.forge = forge;
+    }
+    if let Some(host) = cli.host {
+        config.host = Some(host);
+    }
+    if let Some(api_url) = cli.api_url {
+        config.api_url = Some(api_url);
+    }
+    if let Err(e) = config.validate() {
+        eprintln!("knife: {e}");
+        return Ok(ExitCode::FAILURE);
+    }
+    let token = match token::resolve(cli.token_file.as_deref(), config.forge) {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("knife: {e}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if let Some(command) = cli.command {
+        return Ok(cli::run(command, token, config).await);
+    }
     let terminal = ratatui::init();
-    run(terminal).await?;
+    run(terminal, token, config).await?;
     ratatui::restore();
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
-async fn run(terminal: DefaultTerminal) -> Result<(), Box<dyn std::error::Error>> {
-    App::new().run(terminal).await?;
+async fn run(
+    terminal: DefaultTerminal,
+    token: Option<String>,
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut app = App::with_config(config);
+    // With a token at hand there is nothing to do on the Welcome and Auth screens
+    if let Some(token) = token {
+        app.sign_in(token);
+    }
+    app.run(terminal).await?;
 
     Ok(())
 }
@@ -0,0 +1,221 @@
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::{forge::BoxError, host::Host};
+
+// Listing private repositories needs `repo`, deleting them `delete_repo`
+const SCOPES: &str = "repo delete_repo";
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// GitHub asks to wait this much longer after every `slow_down`
+const SLOW_DOWN_SECONDS: u64 = 5;
+
+// What the user needs to authorize knife, see
+// https://docs.github.com/en/apps/oauth-apps/building-oauth-apps/authorizing-oauth-apps#device-flow
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    // Both in seconds
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+// The answer while polling for the token, either the token or an error code
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+    // Only sent along with `slow_down`
+    interval: Option<u64>,
+}
+
+// Signs in through the OAuth device flow of the OAuth app with the given client ID
+#[derive(Clone)]
+pub struct DeviceFlow {
+    client: Client,
+    client_id: String,
+    base_url: String,
+    // GitHub's intervals are in seconds, tests poll faster
+    second: Duration,
+}
+
+impl DeviceFlow {
+    pub fn new(client_id: &str, host: &Host) -> Self {
+        Self::with_base_url(client_id, &host.web_url)
+    }
+
+    pub fn with_base_url(client_id: &str, base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            client_id: client_id.to_owned(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            second: Duration::from_secs(1),
+        }
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, BoxError> {
+        let res = self
+            .client
+            .post(format!("{}{path}", self.base_url))
+            // Without it GitHub answers form encoded
+            .header("Accept", "application/json")
+            .header("User-Agent", "knife")
+            .form(params)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let error_msg = format!(
+                "Signing in with GitHub failed with status code: {}",
+                res.status()
+            );
+            return Err(error_msg.into());
+        }
+        let body = res.text().await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub async fn request_code(&self) -> Result<DeviceCode, BoxError> {
+        self.post(
+            "/login/device/code",
+            &[("client_id", &self.client_id), ("scope", SCOPES)],
+        )
+        .await
+        // An unknown client ID or a disabled device flow come back as an error code
+        .map_err(|e| format!("Could not get a device code from GitHub: {e}").into())
+    }
+
+    // Polls until the user authorized knife, denied it or the code expired
+    pub async fn poll_token(&self, code: &DeviceCode) -> Result<String, BoxError> {
+        let expires_at = Instant::now() + self.second * code.expires_in as u32;
+        let mut interval = code.interval;
+        loop {
+            tokio::time::sleep(self.second * interval as u32).await;
+            if Instant::now() >= expires_at {
+                return Err("The device code expired, please start again".into());
+            }
+
+            let response: TokenResponse = self
+                .post(
+                    "/login/oauth/access_token",
+                    &[
+                        ("client_id", &self.client_id),
+                        ("device_code", &code.device_code),
+                        ("grant_type", GRANT_TYPE),
+                    ],
+                )
+                .await?;
+            if let Some(token) = response.access_token {
+                return Ok(token);
+            }
+            match response.error.as_deref() {
+                Some("authorization_pending") => {}
+                Some("slow_down") => {
+                    interval = response.interval.unwrap_or(interval + SLOW_DOWN_SECONDS);
+                }
+                Some("expired_token") => {
+                    return Err("The device code expired, please start again".into());
+                }
+                Some("access_denied") => return Err("Authorizing knife was cancelled".into()),
+                Some(error) => {
+                    let description = response.error_description.unwrap_or_default();
+                    return Err(format!("Signing in failed ({error}): {description}").into());
+                }
+                None => return Err("GitHub answered without a token".into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn device_code(interval: u64) -> DeviceCode {
+        DeviceCode {
+            device_code: String::from("device-123"),
+            user_code: String::from("WDJB-MJHT"),
+            verification_uri: String::from("https://github.com/login/device"),
+            expires_in: 900,
+            interval,
+        }
+    }
+
+    async fn respond_once(server: &MockServer, body: serde_json::Value) {
+        Mock::given(method("POST"))
+            .and(path("/login/oauth/access_token"))
+            .and(body_string_contains("device_code=device-123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .up_to_n_times(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_request_code() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/login/device/code"))
+            .and(body_string_contains("client_id=knife-client"))
+            .and(body_string_contains("scope=repo+delete_repo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "device_code": "device-123",
+                "user_code": "WDJB-MJHT",
+                "verification_uri": "https://github.com/login/device",
+                "expires_in": 900,
+                "interval": 5
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let flow = DeviceFlow::with_base_url("knife-client", &server.uri());
+        assert_eq!(flow.request_code().await.unwrap(), device_code(5));
+    }
+
+    // Polls every few milliseconds instead of seconds
+    fn fast_flow(server: &MockServer) -> DeviceFlow {
+        DeviceFlow {
+            second: Duration::from_millis(10),
+            ..DeviceFlow::with_base_url("knife-client", &server.uri())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_token_waits_and_slows_down() {
+        let server = MockServer::start().await;
+        respond_once(&server, json!({ "error": "authorization_pending" })).await;
+        respond_once(&server, json!({ "error": "slow_down" })).await;
+        respond_once(&server, json!({ "access_token": "gho_device" })).await;
+
+        let flow = fast_flow(&server);
+        let started = Instant::now();
+        let token = flow.poll_token(&device_code(1)).await.unwrap();
+        assert_eq!(token, "gho_device");
+        // One second each before the first two polls, six after slowing down
+        assert!(started.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[tokio::test]
+    async fn test_poll_token_fails_when_expired_or_denied() {
+        let server = MockServer::start().await;
+        respond_once(&server, json!({ "error": "expired_token" })).await;
+        respond_once(&server, json!({ "error": "access_denied" })).await;
+
+        let flow = fast_flow(&server);
+        let expired = flow.poll_token(&device_code(1)).await.unwrap_err();
+        assert!(expired.to_string().contains("expired"));
+        let denied = flow.poll_token(&device_code(1)).await.unwrap_err();
+        assert!(denied.to_string().contains("cancelled"));
+    }
+}
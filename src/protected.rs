@@ -0,0 +1,76 @@
+// Repository names or glob patterns from `protected` in the config. Patterns with a '/' match
+// "owner/name", all others only the name. `*` matches any number of characters, `?` exactly one.
+// GitHub treats names case insensitively, so do the patterns.
+pub fn protecting_pattern<'a>(patterns: &'a [String], owner: &str, name: &str) -> Option<&'a str> {
+    let full_name = format!("{owner}/{name}");
+    patterns
+        .iter()
+        .find(|pattern| {
+            let text = if pattern.contains('/') {
+                full_name.as_str()
+            } else {
+                name
+            };
+            glob_match(pattern, text)
+        })
+        .map(String::as_str)
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it currently stands for
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and try again
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("dotfiles", "dotfiles"));
+        assert!(glob_match("dotfiles", "DotFiles"));
+        assert!(!glob_match("dotfiles", "dotfiles-old"));
+        assert!(glob_match("*-prod", "api-prod"));
+        assert!(!glob_match("*-prod", "api-prod-old"));
+        assert!(glob_match("infra-*", "infra-"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("v?", "v2"));
+        assert!(!glob_match("v?", "v"));
+    }
+
+    #[test]
+    fn test_protecting_pattern_matches_name_or_full_name() {
+        let patterns = vec![String::from("dotfiles"), String::from("acme/*")];
+        assert_eq!(
+            protecting_pattern(&patterns, "octocat", "dotfiles"),
+            Some("dotfiles")
+        );
+        assert_eq!(
+            protecting_pattern(&patterns, "acme", "website"),
+            Some("acme/*")
+        );
+        assert_eq!(protecting_pattern(&patterns, "octocat", "website"), None);
+    }
+}
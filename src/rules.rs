@@ -0,0 +1,227 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::ui::Repository;
+
+// A named set of rules, applied together from the rules popup in Select mode
+#[derive(Debug, Clone, Deserialize)]
+pub struct Ruleset {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+// A repository matching the condition gets selected, remembering the rule's name
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub condition: Condition,
+}
+
+// Predicates over a repository. They compose with `all`, `any` and `not`, e.g. in TOML:
+// `condition = { all = [{ not_pushed_for_days = 730 }, { max_stars = 0 }] }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    // Repositories that were never pushed to match any age
+    NotPushedForDays(i64),
+    NotUpdatedForDays(i64),
+    CreatedDaysAgo(i64),
+    MaxStars(u32),
+    // `max_forks = 0` means nobody forked the repository
+    MaxForks(u32),
+    MaxSizeKb(u64),
+    Fork(bool),
+    Archived(bool),
+    Private(bool),
+    // GitHub reports a size of 0 for repositories without any content
+    Empty(bool),
+    Language(String),
+    // Only matches forks whose commits ahead of their parent are known, see
+    // `Condition::needs_upstream_comparison`
+    MaxCommitsAheadOfUpstream(u32),
+}
+
+impl Condition {
+    pub fn matches(
+        &self,
+        repo: &Repository,
+        commits_ahead: Option<u32>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let metadata = &repo.metadata;
+        let older_than = |date: Option<DateTime<Utc>>, days: i64| {
+            date.is_none_or(|date| now.signed_duration_since(date) >= Duration::days(days))
+        };
+
+        match self {
+            Condition::All(conditions) => conditions
+                .iter()
+                .all(|condition| condition.matches(repo, commits_ahead, now)),
+            Condition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.matches(repo, commits_ahead, now)),
+            Condition::Not(condition) => !condition.matches(repo, commits_ahead, now),
+            Condition::NotPushedForDays(days) => older_than(metadata.pushed_at, *days),
+            Condition::NotUpdatedForDays(days) => older_than(metadata.updated_at, *days),
+            Condition::CreatedDaysAgo(days) => {
+                metadata.created_at.is_some() && older_than(metadata.created_at, *days)
+            }
+            Condition::MaxStars(stars) => metadata.stargazers_count <= *stars,
+            Condition::MaxForks(forks) => metadata.forks_count <= *forks,
+            Condition::MaxSizeKb(size) => metadata.size <= *size,
+            Condition::Fork(fork) => metadata.fork == *fork,
+            Condition::Archived(archived) => metadata.archived == *archived,
+            Condition::Private(private) => metadata.private == *private,
+            Condition::Empty(empty) => (metadata.size == 0) == *empty,
+            Condition::Language(language) => metadata
+                .language
+                .as_deref()
+                .is_some_and(|l| l.eq_ignore_ascii_case(language)),
+            Condition::MaxCommitsAheadOfUpstream(max) => {
+                metadata.fork && commits_ahead.is_some_and(|ahead| ahead <= *max)
+            }
+        }
+    }
+
+    // Comparing a fork with its parent costs extra requests, so it is only done for rulesets
+    // that ask for it
+    pub fn needs_upstream_comparison(&self) -> bool {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().any(Condition::needs_upstream_comparison)
+            }
+            Condition::Not(condition) => condition.needs_upstream_comparison(),
+            Condition::MaxCommitsAheadOfUpstream(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Ruleset {
+    // Name of the first rule matching the repository
+    pub fn first_match(
+        &self,
+        repo: &Repository,
+        commits_ahead: Option<u32>,
+        now: DateTime<Utc>,
+    ) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.condition.matches(repo, commits_ahead, now))
+            .map(|rule| rule.name.as_str())
+    }
+
+    pub fn needs_upstream_comparison(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.condition.needs_upstream_comparison())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::ui::{RepositoryMetadata, Status};
+    use chrono::TimeZone;
+
+    const RULESETS: &str = r#"
+        [[rulesets]]
+        name = "cleanup"
+
+        [[rulesets.rules]]
+        name = "abandoned"
+        condition = { all = [
+            { not_pushed_for_days = 730 },
+            { max_stars = 0 },
+            { max_forks = 0 },
+            { max_size_kb = 1024 },
+        ] }
+
+        [[rulesets.rules]]
+        name = "unchanged fork"
+        condition = { all = [{ fork = true }, { max_commits_ahead_of_upstream = 0 }] }
+
+        [[rulesets.rules]]
+        name = "archived and empty"
+        condition = { all = [{ archived = true }, { empty = true }] }
+    "#;
+
+    fn repository(metadata: RepositoryMetadata) -> Repository {
+        Repository {
+            name: String::from("repo"),
+            owner: String::from("octocat"),
+            status: Status::Unselected,
+            metadata,
+            matched_rule: None,
+        }
+    }
+
+    #[test]
+    fn test_rulesets_match_first_rule() {
+        let config = config::parse(RULESETS).unwrap();
+        let ruleset = &config.rulesets[0];
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+
+        let abandoned = repository(RepositoryMetadata {
+            pushed_at: Some(now - Duration::days(800)),
+            size: 12,
+            ..RepositoryMetadata::default()
+        });
+        assert_eq!(
+            ruleset.first_match(&abandoned, None, now),
+            Some("abandoned")
+        );
+
+        let starred = repository(RepositoryMetadata {
+            pushed_at: Some(now - Duration::days(800)),
+            stargazers_count: 3,
+            size: 12,
+            ..RepositoryMetadata::default()
+        });
+        assert_eq!(ruleset.first_match(&starred, None, now), None);
+
+        let fork = repository(RepositoryMetadata {
+            pushed_at: Some(now),
+            fork: true,
+            size: 12,
+            ..RepositoryMetadata::default()
+        });
+        assert!(ruleset.needs_upstream_comparison());
+        assert_eq!(ruleset.first_match(&fork, None, now), None);
+        assert_eq!(ruleset.first_match(&fork, Some(2), now), None);
+        assert_eq!(
+            ruleset.first_match(&fork, Some(0), now),
+            Some("unchanged fork")
+        );
+
+        let archived = repository(RepositoryMetadata {
+            pushed_at: Some(now),
+            stargazers_count: 10,
+            archived: true,
+            ..RepositoryMetadata::default()
+        });
+        assert_eq!(
+            ruleset.first_match(&archived, None, now),
+            Some("archived and empty")
+        );
+    }
+
+    #[test]
+    fn test_not_inverts_condition() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let condition: Condition =
+            toml::from_str::<Rule>("name = \"x\"\ncondition = { not = { language = \"rust\" } }")
+                .unwrap()
+                .condition;
+        let rust = repository(RepositoryMetadata {
+            language: Some(String::from("Rust")),
+            ..RepositoryMetadata::default()
+        });
+        assert!(!condition.matches(&rust, None, now));
+        assert!(condition.matches(&repository(RepositoryMetadata::default()), None, now));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::ui::Repository;
+
+// Characters after which a match counts as the start of a word
+const WORD_SEPARATORS: [char; 5] = ['-', '_', '/', '.', ' '];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    // Char indices of the matched characters in the searched text
+    pub positions: Vec<usize>,
+}
+
+// Matches the query as a case insensitive subsequence of the text. Whitespace in the query is
+// ignored. Consecutive characters and matches at word starts score higher, gaps score lower.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let text: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = text
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let first = *query.first()?;
+
+    // Try every occurrence of the first query character as a starting point and keep the best
+    // scoring one, so "kn" highlights "knife" rather than the "k" and "n" of "backend".
+    lowered
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c == first)
+        .filter_map(|(start, _)| match_from(&query, &text, &lowered, start))
+        .max_by_key(|found| found.score)
+}
+
+fn match_from(query: &[char], text: &[char], lowered: &[char], start: usize) -> Option<FuzzyMatch> {
+    let mut positions = Vec::with_capacity(query.len());
+    let mut next = start;
+    for c in query {
+        let offset = lowered[next..].iter().position(|l| l == c)?;
+        positions.push(next + offset);
+        next += offset + 1;
+    }
+
+    let mut score = 0;
+    for (i, &position) in positions.iter().enumerate() {
+        score += 1;
+        if position == 0 || WORD_SEPARATORS.contains(&text[position - 1]) {
+            score += 3;
+        }
+        if i > 0 {
+            let gap = position - positions[i - 1] - 1;
+            if gap == 0 {
+                score += 5;
+            } else {
+                score -= gap as i64;
+            }
+        }
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+// Returns the positions to highlight in the repository name if the query matches its name,
+// description or one of its topics. Matches outside of the name have nothing to highlight.
+pub fn match_repository(query: &str, repo: &Repository) -> Option<Vec<usize>> {
+    if query.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    if let Some(found) = fuzzy_match(query, &repo.name) {
+        return Some(found.positions);
+    }
+
+    let metadata = &repo.metadata;
+    let in_description = metadata
+        .description
+        .as_deref()
+        .is_some_and(|description| fuzzy_match(query, description).is_some());
+    let in_topics = metadata
+        .topics
+        .iter()
+        .any(|topic| fuzzy_match(query, topic).is_some());
+
+    (in_description || in_topics).then(Vec::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{RepositoryMetadata, Status};
+
+    fn repository(name: &str, description: Option<&str>, topics: &[&str]) -> Repository {
+        Repository {
+            name: name.to_owned(),
+            owner: String::from("octocat"),
+            status: Status::Unselected,
+            metadata: RepositoryMetadata {
+                description: description.map(str::to_owned),
+                topics: topics.iter().map(|topic| topic.to_string()).collect(),
+                ..RepositoryMetadata::default()
+            },
+            matched_rule: None,
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_match_is_case_insensitive_subsequence() {
+        let found = fuzzy_match("KnF", "knife").unwrap();
+        assert_eq!(found.positions, vec![0, 1, 3]);
+        assert!(fuzzy_match("fk", "knife").is_none());
+        assert!(fuzzy_match("", "knife").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match_prefers_consecutive_word_start() {
+        let found = fuzzy_match("kn", "backend-knife").unwrap();
+        assert_eq!(found.positions, vec![8, 9]);
+    }
+
+    #[test]
+    fn test_match_repository_searches_description_and_topics() {
+        let repo = repository("dotfiles", Some("My vim setup"), &["neovim"]);
+        assert_eq!(match_repository("dot", &repo), Some(vec![0, 1, 2]));
+        assert_eq!(match_repository("vim setup", &repo), Some(Vec::new()));
+        assert_eq!(match_repository("neo", &repo), Some(Vec::new()));
+        assert_eq!(match_repository("rust", &repo), None);
+        assert_eq!(match_repository(" ", &repo), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::AbortHandle;
+
+use crate::{
+    app::Error,
+    batch::Report,
+    forge::{BoxError, ForgeError, RepositoryDetails, TokenInfo},
+    oauth::DeviceCode,
+    ui::{Repository, RepositoryListing},
+};
+
+// Results of forge requests running in the background, sent back to the event loop
+pub enum TaskResult {
+    // The owner of the token and what it may do, checked before loading the repositories
+    TokenChecked(Result<TokenInfo, ForgeError>),
+    // Logins of the user's organizations, offered to pick from along with their own account
+    Organizations(Result<Vec<String>, ForgeError>),
+    // A fresh listing, either after entering the token or after changing the filter
+    Repositories(Result<RepositoryListing, Error>),
+    // Another page of the listing with the given generation, see `App::repos_generation`
+    Page {
+        generation: u64,
+        result: Result<(Vec<Repository>, Option<String>), ForgeError>,
+    },
+    // Details of the given repository. The client caches them, so only failures are of interest.
+    Details {
+        owner: String,
+        name: String,
+        result: Result<Option<RepositoryDetails>, ForgeError>,
+    },
+    // Commits ahead of upstream per full repository name, for forks that could be compared
+    UpstreamCompared {
+        ruleset: usize,
+        commits_ahead: HashMap<String, u32>,
+    },
+    // One repository of the batch is done, see `batch::run`
+    Changed(Report),
+    BatchFinished,
+    // The device flow started, the code is shown until the user entered it on GitHub
+    DeviceCode(Result<DeviceCode, BoxError>),
+    DeviceToken(Result<String, BoxError>),
+}
+
+// The operation the user is waiting for. Only one runs at a time and it can be cancelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Activity {
+    CheckingToken,
+    LoadingOrganizations,
+    LoadingRepositories,
+    ApplyingRuleset,
+    Changing { done: usize, total: usize },
+    RequestingDeviceCode,
+    WaitingForAuthorization,
+}
+
+impl Activity {
+    pub fn describe(&self) -> String {
+        match self {
+            Activity::CheckingToken => String::from("Checking the token"),
+            Activity::LoadingOrganizations => String::from("Loading your organizations"),
+            Activity::LoadingRepositories => String::from("Loading repositories"),
+            Activity::ApplyingRuleset => String::from("Comparing forks with their upstream"),
+            Activity::Changing { done, total } => {
+                format!("Changing repositories ({done}/{total})")
+            }
+            Activity::RequestingDeviceCode => String::from("Asking GitHub for a code"),
+            Activity::WaitingForAuthorization => {
+                String::from("Waiting for you to authorize knife on GitHub")
+            }
+        }
+    }
+}
+
+// Runs the future on the runtime and sends its result to the event loop. Dropping the returned
+// handle does not stop the task, aborting it does.
+pub fn spawn<F>(sender: &UnboundedSender<TaskResult>, future: F) -> AbortHandle
+where
+    F: Future<Output = TaskResult> + Send + 'static,
+{
+    let sender = sender.clone();
+    tokio::spawn(async move {
+        // The receiver only goes away when the app shuts down
+        let _ = sender.send(future.await);
+    })
+    .abort_handle()
+}
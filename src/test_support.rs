@@ -0,0 +1,33 @@
+// Fixtures shared by the tests of several modules
+use wiremock::MockServer;
+
+use crate::{
+    batch::{Action, Change},
+    github::RepositoryClient,
+    ui::{Repository, RepositoryMetadata, Status},
+};
+
+// An unselected repository of octocat without any metadata
+pub fn repository(name: &str) -> Repository {
+    Repository {
+        name: name.to_owned(),
+        owner: String::from("octocat"),
+        status: Status::Unselected,
+        metadata: RepositoryMetadata::default(),
+        matched_rule: None,
+    }
+}
+
+pub fn change(action: Action, name: &str) -> Change {
+    Change {
+        action,
+        owner: String::from("octocat"),
+        name: name.to_owned(),
+        clone_url: format!("https://github.com/octocat/{name}.git"),
+    }
+}
+
+// A GitHub client sending its requests to the mock server
+pub fn github_client(server: &MockServer) -> RepositoryClient {
+    RepositoryClient::with_base_url("token", &server.uri())
+}
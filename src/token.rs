@@ -0,0 +1,132 @@
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::forge::ForgeKind;
+
+// The token to sign in with, if there is one. A `--token-file` wins over the environment, "-"
+// reads the token from stdin.
+pub fn resolve(token_file: Option<&Path>, forge: ForgeKind) -> io::Result<Option<String>> {
+    resolve_from(token_file, forge, |name| env::var(name).ok(), io::stdin())
+}
+
+fn resolve_from(
+    token_file: Option<&Path>,
+    forge: ForgeKind,
+    variable: impl Fn(&str) -> Option<String>,
+    mut stdin: impl Read,
+) -> io::Result<Option<String>> {
+    if let Some(path) = token_file {
+        let stdin_path = path == Path::new("-");
+        let content = if stdin_path {
+            let mut content = String::new();
+            stdin.read_to_string(&mut content)?;
+            content
+        } else {
+            fs::read_to_string(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("reading {}: {e}", path.display())))?
+        };
+        // An empty file is a mistake, falling back to the environment would hide it
+        return match clean(&content) {
+            Some(token) => Ok(Some(token)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                if stdin_path {
+                    String::from("no token on stdin")
+                } else {
+                    format!("no token in {}", path.display())
+                },
+            )),
+        };
+    }
+
+    Ok(forge
+        .token_variables()
+        .iter()
+        .find_map(|name| variable(name).and_then(|value| clean(&value))))
+}
+
+// Files usually end with a newline and pasted values sometimes with a space
+fn clean(token: &str) -> Option<String> {
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_variables(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_token_from_environment() {
+        let both = |name: &str| Some(format!("{name}-value"));
+        assert_eq!(
+            resolve_from(None, ForgeKind::Github, both, io::empty())
+                .unwrap()
+                .as_deref(),
+            Some("GITHUB_TOKEN-value")
+        );
+        let gh_only = |name: &str| match name {
+            "GITHUB_TOKEN" => Some(String::from("  ")),
+            _ => Some(String::from("gho_token\n")),
+        };
+        assert_eq!(
+            resolve_from(None, ForgeKind::Github, gh_only, io::empty())
+                .unwrap()
+                .as_deref(),
+            Some("gho_token")
+        );
+        assert_eq!(
+            resolve_from(None, ForgeKind::Github, no_variables, io::empty()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_token_from_file_or_stdin() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("token");
+        let fine_grained = format!("github_pat_{}", "A1b2_".repeat(16));
+        fs::write(&file, format!("{fine_grained}\n")).unwrap();
+        let environment = |_: &str| Some(String::from("ghp_environment"));
+
+        assert_eq!(
+            resolve_from(Some(&file), ForgeKind::Github, environment, io::empty()).unwrap(),
+            Some(fine_grained)
+        );
+        assert_eq!(
+            resolve_from(
+                Some(Path::new("-")),
+                ForgeKind::Github,
+                no_variables,
+                "ghp_stdin\n".as_bytes()
+            )
+            .unwrap()
+            .as_deref(),
+            Some("ghp_stdin")
+        );
+        assert!(
+            resolve_from(
+                Some(Path::new("-")),
+                ForgeKind::Github,
+                environment,
+                io::empty()
+            )
+            .is_err()
+        );
+        assert!(
+            resolve_from(
+                Some(&directory.path().join("missing")),
+                ForgeKind::Github,
+                no_variables,
+                io::empty()
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,39 +1,264 @@
+use chrono::{DateTime, Utc};
 use ratatui::{
     Frame,
     buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Flex, Layout, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{
-        Block, Borders, HighlightSpacing, List, ListItem, ListState, Paragraph, StatefulWidget,
-        Wrap,
+        Block, Borders, Clear, Gauge, HighlightSpacing, List, ListItem, ListState, Paragraph,
+        StatefulWidget, Widget, Wrap,
     },
 };
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
+use crate::{
+    app::Error,
+    backup::Backup,
+    batch::{Action, Report},
+    forge::{ChangeResult, ForgeKind, Outcome, RepoQuery, RepositoryDetails, TokenInfo},
+    http::RateLimit,
+    oauth::DeviceCode,
+    protected::protecting_pattern,
+    rules::Ruleset,
+    search::match_repository,
+};
+
+// Longer names are not truncated, they just push their own metadata further right
+const MAX_NAME_WIDTH: usize = 40;
 const SELECTED_STYLE: Style = Style::new().bg(Color::Gray).add_modifier(Modifier::BOLD);
 const TEXT_FG_COLOR: Color = Color::White;
 const COMPLETED_TEXT_FG_COLOR: Color = Color::LightCyan;
+const SEARCH_MATCH_FG_COLOR: Color = Color::Yellow;
+const RULE_FG_COLOR: Color = Color::Magenta;
+const ARCHIVE_FG_COLOR: Color = Color::LightBlue;
+const PRIVATE_FG_COLOR: Color = Color::LightGreen;
+const DRY_RUN_FG_COLOR: Color = Color::Yellow;
 pub const LIGHT_RED: Color = Color::LightRed;
 pub const DARK_GRAY: Color = Color::DarkGray;
 
-pub struct GithubContent {
+pub struct RepositoryListing {
     pub owner: String,
     pub repos: Vec<Repository>,
     pub list_state: ListState,
+    // URL of the next page of repositories, if the forge has more to send
+    pub next_page: Option<String>,
+    // Visibility and affiliations the repositories were fetched with
+    pub query: RepoQuery,
+    // Fuzzy search the list is currently filtered by, empty shows everything
+    pub search: String,
+    // Repositories shown in the list. The list state indexes into this, not into `repos`.
+    pub visible: Vec<VisibleRepository>,
+    pub sort_key: SortKey,
+    pub sort_ascending: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    PushedAt,
+    UpdatedAt,
+    CreatedAt,
+    Size,
+    Stars,
+}
+
+impl SortKey {
+    pub fn next(&self) -> Self {
+        match self {
+            SortKey::Name => SortKey::PushedAt,
+            SortKey::PushedAt => SortKey::UpdatedAt,
+            SortKey::UpdatedAt => SortKey::CreatedAt,
+            SortKey::CreatedAt => SortKey::Size,
+            SortKey::Size => SortKey::Stars,
+            SortKey::Stars => SortKey::Name,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::PushedAt => "last push",
+            SortKey::UpdatedAt => "last update",
+            SortKey::CreatedAt => "creation",
+            SortKey::Size => "size",
+            SortKey::Stars => "stars",
+        }
+    }
+
+    // Ascending order. Repositories without a date come first, as if they were the oldest.
+    fn compare(&self, a: &Repository, b: &Repository) -> Ordering {
+        let (a_meta, b_meta) = (&a.metadata, &b.metadata);
+        let ordering = match self {
+            SortKey::Name => Ordering::Equal,
+            SortKey::PushedAt => a_meta.pushed_at.cmp(&b_meta.pushed_at),
+            SortKey::UpdatedAt => a_meta.updated_at.cmp(&b_meta.updated_at),
+            SortKey::CreatedAt => a_meta.created_at.cmp(&b_meta.created_at),
+            SortKey::Size => a_meta.size.cmp(&b_meta.size),
+            SortKey::Stars => a_meta.stargazers_count.cmp(&b_meta.stargazers_count),
+        };
+        ordering.then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VisibleRepository {
+    // Index into `RepositoryListing::repos`
+    pub index: usize,
+    // Char indices of the repository name matched by the search
+    pub highlights: Vec<usize>,
+}
+
+impl RepositoryListing {
+    pub fn new(
+        owner: &str,
+        repos: Vec<Repository>,
+        next_page: Option<String>,
+        query: RepoQuery,
+    ) -> Self {
+        let mut listing = Self {
+            owner: owner.to_owned(),
+            repos,
+            list_state: ListState::default(),
+            next_page,
+            query,
+            search: String::new(),
+            visible: Vec::new(),
+            sort_key: SortKey::default(),
+            sort_ascending: true,
+        };
+        listing.refresh_view();
+        listing
+    }
+
+    // Index into `repos` of the repository under the cursor
+    pub fn highlighted_index(&self) -> Option<usize> {
+        let i = self.list_state.selected()?;
+        self.visible.get(i).map(|visible| visible.index)
+    }
+
+    pub fn highlighted(&self) -> Option<&Repository> {
+        self.highlighted_index().map(|i| &self.repos[i])
+    }
+
+    pub fn set_search(&mut self, search: &str) {
+        self.search = search.to_owned();
+        self.refresh_view();
+    }
+
+    pub fn cycle_sort_key(&mut self) {
+        self.sort_key = self.sort_key.next();
+        self.refresh_view();
+    }
+
+    pub fn toggle_sort_direction(&mut self) {
+        self.sort_ascending = !self.sort_ascending;
+        self.refresh_view();
+    }
+
+    // Recomputes the visible repositories, e.g. after the search, the sort order or the
+    // repositories changed. The cursor stays on the same repository if it is still visible.
+    pub fn refresh_view(&mut self) {
+        let highlighted = self.highlighted_index();
+        self.visible = self
+            .repos
+            .iter()
+            .enumerate()
+            .filter_map(|(index, repo)| {
+                match_repository(&self.search, repo)
+                    .map(|highlights| VisibleRepository { index, highlights })
+            })
+            .collect();
+        self.visible.sort_by(|a, b| {
+            let ordering = self
+                .sort_key
+                .compare(&self.repos[a.index], &self.repos[b.index]);
+            if self.sort_ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        let position = highlighted.and_then(|index| {
+            self.visible
+                .iter()
+                .position(|visible| visible.index == index)
+        });
+        match (position, highlighted) {
+            (Some(position), _) => self.list_state.select(Some(position)),
+            (None, Some(_)) if !self.visible.is_empty() => self.list_state.select(Some(0)),
+            (None, Some(_)) => self.list_state.select(None),
+            (None, None) => {}
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Repository {
     pub name: String,
+    pub owner: String,
     pub status: Status,
+    pub metadata: RepositoryMetadata,
+    // Name of the rule that selected the repository, `None` when selected by hand
+    pub matched_rule: Option<String>,
+}
+
+// Everything GitHub tells us about a repository that helps deciding whether it can go
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RepositoryMetadata {
+    // `None` for repositories that never received a push
+    pub pushed_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub stargazers_count: u32,
+    pub forks_count: u32,
+    // Size in kilobytes
+    pub size: u64,
+    pub fork: bool,
+    pub archived: bool,
+    pub private: bool,
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub topics: Vec<String>,
+    pub default_branch: String,
+    pub clone_url: String,
+    // What the user may do with the repository, `None` if GitHub didn't say
+    pub permissions: Option<Permissions>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Permissions {
+    pub admin: bool,
+}
+
+impl RepositoryMetadata {
+    // Deleting, archiving and changing the visibility all need admin rights. Organization
+    // members often only have write access.
+    pub fn can_administer(&self) -> bool {
+        self.permissions
+            .as_ref()
+            .is_none_or(|permissions| permissions.admin)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Status {
-    Selected,
+    // Marked for the action, which runs once the user confirms
+    Marked(Action),
     Unselected,
 }
 
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+pub fn spinner(frame: usize) -> &'static str {
+    SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]
+}
+
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     // Cut the given rectangle into three vertical pieces
     let popup_layout = Layout::default()
@@ -78,12 +303,18 @@ pub fn draw_token_input(
         .borders(Borders::ALL)
         .border_style(style);
 
-    let token_text = Paragraph::new(input).style(style).block(key_block);
+    // Fine-grained tokens can be wider than the field, keep the cursor in view by scrolling
+    let width = input_area.width.saturating_sub(3);
+    let scroll = character_index.saturating_sub(width);
+    let token_text = Paragraph::new(input)
+        .style(style)
+        .block(key_block)
+        .scroll((0, scroll));
     frame.render_widget(token_text, input_area);
     frame.set_cursor_position(Position::new(
         // Draw the cursor at the current position in the input field.
         // This position can be controlled via the left and right arrow key
-        input_area.x + character_index + 1,
+        input_area.x + character_index - scroll + 1,
         // Move one line down, from the border to the input line
         input_area.y + 1,
     ));
@@ -97,33 +328,320 @@ pub fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
     area
 }
 
-pub fn render_selected_repositories(frame: &mut Frame, repos: &[Repository]) {
-    let selected_repos: Vec<String> = repos
-        .iter()
-        .filter(|r| r.status == Status::Selected)
-        .map(|r| r.name.clone())
-        .collect();
+// Groups the marked repositories by what is going to happen to them
+pub fn render_selected_repositories(
+    frame: &mut Frame,
+    repos: &[Repository],
+    backup: Option<&Backup>,
+) {
+    let mut lines = Vec::new();
+    for action in Action::ALL {
+        let marked: Vec<String> = repos
+            .iter()
+            .filter(|r| r.status == Status::Marked(action))
+            .map(|r| match &r.matched_rule {
+                Some(rule) => format!("{} ({rule})", r.name),
+                None => r.name.clone(),
+            })
+            .collect();
+        if marked.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::styled(
+            format!("{}:", action.pending()),
+            Style::default()
+                .fg(TEXT_FG_COLOR)
+                .add_modifier(Modifier::BOLD),
+        ));
+        lines.push(Line::styled(
+            marked.join(", "),
+            Style::default().fg(action_color(action)),
+        ));
+        if action == Action::Delete
+            && let Some(backup) = backup
+        {
+            lines.push(Line::styled(
+                format!("after a backup to {}", backup.directory.display()),
+                Style::default().fg(DARK_GRAY),
+            ));
+        }
+    }
 
-    let content = selected_repos.join(", ");
-    let text = Text::from(content);
-    let paragraph = Paragraph::new(text)
+    let paragraph = Paragraph::new(Text::from(lines))
         .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::Red))
         .wrap(Wrap { trim: true });
     let area = popup_area(frame.area(), 80, 40);
     frame.render_widget(paragraph, area);
 }
 
-pub fn render_all_repositories(github_content: &mut GithubContent, area: Rect, buf: &mut Buffer) {
+fn action_color(action: Action) -> Color {
+    match action {
+        Action::Delete => Color::Red,
+        Action::Archive | Action::Unarchive => ARCHIVE_FG_COLOR,
+        Action::MakePrivate => PRIVATE_FG_COLOR,
+    }
+}
+
+pub fn render_batch_progress(done: usize, total: usize, area: Rect, buf: &mut Buffer) {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        done as f64 / total as f64
+    };
+    Gauge::default()
+        .block(Block::bordered().title("Applying changes"))
+        .gauge_style(Style::default().fg(LIGHT_RED))
+        .label(format!("{done}/{total}"))
+        .ratio(ratio)
+        .render(area, buf);
+}
+
+// The one-time code of the device flow and where to enter it
+pub fn render_device_code(device_code: &DeviceCode, area: Rect, buf: &mut Buffer) {
+    let text = vec![
+        Line::from(format!(
+            "Open {} and enter this code:",
+            device_code.verification_uri
+        )),
+        Line::from(""),
+        Line::from(device_code.user_code.clone().bold().fg(LIGHT_RED)),
+        Line::from(""),
+        Line::from(format!(
+            "The code expires in {} minutes.",
+            device_code.expires_in / 60
+        ))
+        .fg(DARK_GRAY),
+    ];
+    Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .render(area, buf);
+}
+
+// Counts the results per action and lists every repository of the last batch, with GitHub's
+// explanation where something went wrong
+pub fn render_summary(reports: &[Report], area: Rect, buf: &mut Buffer) {
+    let counts: Vec<String> = Action::ALL
+        .iter()
+        .filter_map(|action| {
+            let reports = reports
+                .iter()
+                .filter(|report| report.change.action == *action);
+            let total = reports.clone().count();
+            let count = |outcome| {
+                reports
+                    .clone()
+                    .filter(|report| report.result.outcome == outcome)
+                    .count()
+            };
+            let (succeeded, planned) = (count(Outcome::Succeeded), count(Outcome::DryRun));
+            let past_tense = action.past_tense();
+            match (total, planned) {
+                (0, _) => None,
+                (_, 0) => Some(format!("{past_tense} {succeeded} of {total}")),
+                _ => Some(format!("{past_tense} {planned} of {total} in the dry run")),
+            }
+        })
+        .collect();
+    // Failures first, they are what needs attention
+    let mut sorted: Vec<&Report> = reports.iter().collect();
+    sorted.sort_by_key(|report| {
+        matches!(report.result.outcome, Outcome::Succeeded | Outcome::DryRun)
+    });
+
+    let mut lines = vec![
+        Line::from(format!("Repositories {}", counts.join(", "))),
+        Line::default(),
+    ];
+    for report in sorted {
+        let color = match report.result.outcome {
+            Outcome::Succeeded => COMPLETED_TEXT_FG_COLOR,
+            Outcome::DryRun => DRY_RUN_FG_COLOR,
+            _ => LIGHT_RED,
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!(
+                    "{}/{} ({})",
+                    report.change.owner,
+                    report.change.name,
+                    report.change.action.past_tense()
+                ),
+                Style::default().fg(TEXT_FG_COLOR),
+            ),
+            Span::styled(
+                format!(" {}", outcome_text(&report.result)),
+                Style::default().fg(color),
+            ),
+        ]));
+        if let Some(folder) = &report.backup {
+            lines.push(Line::styled(
+                format!("  backup in {}", folder.display()),
+                Style::default().fg(DARK_GRAY),
+            ));
+        }
+    }
+
+    Paragraph::new(lines)
+        .block(Block::bordered().title("Summary"))
+        .wrap(Wrap { trim: true })
+        .render(area, buf);
+}
+
+pub fn render_dry_run_banner(forge: ForgeKind, area: Rect, buf: &mut Buffer) {
+    Paragraph::new(format!(
+        " DRY RUN · nothing on {} will be changed ",
+        forge.name()
+    ))
+    .alignment(Alignment::Center)
+    .style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(DRY_RUN_FG_COLOR)
+            .add_modifier(Modifier::BOLD),
+    )
+    .render(area, buf);
+}
+
+// Tokens expiring within this many days are highlighted on the status line
+const TOKEN_EXPIRY_WARNING_DAYS: i64 = 7;
+// The API quota is highlighted once less than this share of it is left
+const LOW_QUOTA_SHARE: f64 = 0.1;
+
+// Who is signed in, with what scopes and until when, and how much of the API quota is left.
+// Missing scopes, a close expiry and rate limits stand out.
+pub fn render_status_line(
+    token_info: &TokenInfo,
+    rate_limit: &RateLimit,
+    now: DateTime<Utc>,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let mut spans =
+        vec![Span::raw(format!("Signed in as {}", token_info.describe())).fg(DARK_GRAY)];
+    let missing_scopes = token_info.missing_scopes();
+    if !missing_scopes.is_empty() {
+        spans.push(Span::raw(format!(" · missing {}", missing_scopes.join(", "))).fg(LIGHT_RED));
+    }
+    if let Some(expires_at) = token_info.expires_at
+        && (expires_at - now).num_days() < TOKEN_EXPIRY_WARNING_DAYS
+    {
+        spans.push(Span::raw(" · expires soon").fg(LIGHT_RED));
+    }
+    if let Some(quota) = rate_limit.quota {
+        let text = format!(
+            " · API quota {}/{}, resets in {}",
+            quota.remaining,
+            quota.limit,
+            minutes_until(quota.reset, now)
+        );
+        let low = (quota.remaining as f64) < quota.limit as f64 * LOW_QUOTA_SHARE;
+        spans.push(Span::raw(text).fg(if low { LIGHT_RED } else { DARK_GRAY }));
+    }
+    if let Some(waiting_until) = rate_limit.waiting_until {
+        spans.push(
+            Span::raw(format!(
+                " · rate limited, waiting {}",
+                minutes_until(waiting_until, now)
+            ))
+            .fg(LIGHT_RED),
+        );
+    }
+    Paragraph::new(Line::from(spans))
+        .alignment(Alignment::Center)
+        .render(area, buf);
+}
+
+// E.g. "forbidden (403): Must have admin rights to Repository."
+fn outcome_text(result: &ChangeResult) -> String {
+    if result.outcome == Outcome::Succeeded {
+        return String::from(result.outcome.label());
+    }
+    let mut reason = String::from(result.outcome.label());
+    if let Some(status) = result.status {
+        reason.push_str(&format!(" ({})", status.as_u16()));
+    }
+    if let Some(message) = &result.message {
+        reason.push_str(&format!(": {message}"));
+    }
+    reason
+}
+
+pub fn render_all_repositories(
+    listing: &mut RepositoryListing,
+    protected: &[String],
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let query = &listing.query;
+    let (whose, filter) = match &query.organization {
+        Some(organization) => (
+            format!("{organization}'s"),
+            query.visibility.as_str().to_owned(),
+        ),
+        None => (
+            String::from("Your"),
+            format!(
+                "{} · {}",
+                query.visibility.as_str(),
+                query.affiliation_param().replace(',', ", ")
+            ),
+        ),
+    };
+    let direction = if listing.sort_ascending { "↑" } else { "↓" };
+    let mut title = format!(
+        "{whose} repositories [{filter}] sorted by {} {direction}",
+        listing.sort_key.label()
+    );
+    if !listing.search.is_empty() {
+        title.push_str(&format!(
+            " ({} of {} match)",
+            listing.visible.len(),
+            listing.repos.len()
+        ));
+    }
+    if listing.next_page.is_some() {
+        title.push_str(&format!(
+            " (loaded {}, fetching more...)",
+            listing.repos.len()
+        ));
+    }
     let block = Block::new()
-        .title(Line::raw("Your public repositories").centered())
+        .title(Line::raw(title).centered())
         .borders(Borders::TOP)
         .style(Style::default().fg(Color::LightRed));
 
-    let items: Vec<ListItem> = github_content
-        .repos
+    let now = Utc::now();
+    let name_width = listing
+        .visible
+        .iter()
+        .map(|visible| {
+            display_name(&listing.repos[visible.index], &listing.owner)
+                .chars()
+                .count()
+        })
+        .max()
+        .unwrap_or(0)
+        .min(MAX_NAME_WIDTH);
+    let items: Vec<ListItem> = listing
+        .visible
         .iter()
-        .map(|repo_item| ListItem::from(repo_item).bg(Color::Reset))
+        .map(|visible| {
+            let repo_item = &listing.repos[visible.index];
+            let locked = protecting_pattern(protected, &repo_item.owner, &repo_item.name).is_some()
+                || !repo_item.metadata.can_administer();
+            repository_item(
+                repo_item,
+                &listing.owner,
+                &visible.highlights,
+                name_width,
+                locked,
+                now,
+            )
+            .bg(Color::Reset)
+        })
         .collect();
 
     let list = List::new(items)
@@ -134,15 +652,434 @@ pub fn render_all_repositories(github_content: &mut GithubContent, area: Rect, b
 
     // We need to disambiguate this trait method as both `Widget` and `StatefulWidget` share the
     // same method name `render`.
-    StatefulWidget::render(list, area, buf, &mut github_content.list_state);
+    StatefulWidget::render(list, area, buf, &mut listing.list_state);
+}
+
+pub fn render_repository_details(
+    repo: Option<&Repository>,
+    details: Option<&RepositoryDetails>,
+    error: Option<&str>,
+    // The details of `repo` are on their way
+    loading: bool,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let block = Block::new()
+        .title(Line::raw("Details").centered())
+        .borders(Borders::TOP | Borders::LEFT)
+        .style(Style::default().fg(Color::LightRed));
+
+    let lines = match (repo, details, error) {
+        (None, _, _) => vec![Line::from(
+            "Move the cursor to a repository to see its details.",
+        )],
+        (Some(_), None, Some(error)) => vec![Line::from(Span::styled(
+            format!("Loading details failed: {error}"),
+            Style::default().fg(Color::Red),
+        ))],
+        (Some(_), None, None) if loading => vec![Line::from("Loading…").fg(DARK_GRAY)],
+        (Some(_), None, None) => vec![Line::from("No details available.")],
+        (Some(repo), Some(details), _) => details_lines(repo, details, Utc::now()),
+    };
+
+    Paragraph::new(Text::from(lines))
+        .style(Style::default().fg(TEXT_FG_COLOR))
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .render(area, buf);
+}
+
+fn details_lines<'a>(
+    repo: &Repository,
+    details: &RepositoryDetails,
+    now: DateTime<Utc>,
+) -> Vec<Line<'a>> {
+    let label = |text: &str| Span::styled(format!("{text}: "), DARK_GRAY);
+    let mut lines = vec![Line::from(Span::styled(
+        format!("{}/{}", repo.owner, repo.name),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+
+    if let Some(description) = &details.description {
+        lines.push(Line::from(description.clone()));
+    }
+    if !details.topics.is_empty() {
+        lines.push(Line::from(vec![
+            label("Topics"),
+            Span::raw(details.topics.join(", ")),
+        ]));
+    }
+    if let Some(homepage) = &details.homepage {
+        lines.push(Line::from(vec![
+            label("Homepage"),
+            Span::raw(homepage.clone()),
+        ]));
+    }
+    lines.push(Line::from(vec![
+        label("Open issues"),
+        Span::raw(format!("{} · ", details.open_issues)),
+        label("Open PRs"),
+        Span::raw(details.open_pull_requests.to_string()),
+    ]));
+    lines.push(Line::from(vec![
+        label("Branches"),
+        Span::raw(format!("{} · ", details.branches)),
+        label("Releases"),
+        Span::raw(details.releases.to_string()),
+    ]));
+    let latest_commit = match &details.latest_commit {
+        Some(commit) => {
+            let subject = commit.message.lines().next().unwrap_or_default();
+            match commit.date {
+                Some(date) => format!("{subject} ({})", relative_time(date, now)),
+                None => subject.to_owned(),
+            }
+        }
+        None => String::from("none, the repository is empty"),
+    };
+    lines.push(Line::from(vec![
+        label("Last commit"),
+        Span::raw(latest_commit),
+    ]));
+    if details.is_template {
+        lines.push(Line::from("Template repository"));
+    }
+    if let Some(parent) = &details.parent {
+        lines.push(Line::from(vec![
+            label("Fork of"),
+            Span::raw(parent.clone()),
+        ]));
+    }
+    if let Some(readme) = &details.readme_excerpt {
+        lines.push(Line::from(label("README")));
+        lines.extend(
+            readme
+                .lines()
+                .map(|line| Line::styled(line.to_owned(), DARK_GRAY)),
+        );
+    }
+    lines
+}
+
+// Repositories of other owners (collaborations, organizations) are shown with their owner
+pub fn display_name(repo: &Repository, account: &str) -> String {
+    if repo.owner == account {
+        repo.name.clone()
+    } else {
+        format!("{}/{}", repo.owner, repo.name)
+    }
+}
+
+fn repository_item<'a>(
+    repo: &Repository,
+    account: &str,
+    highlights: &[usize],
+    name_width: usize,
+    locked: bool,
+    now: DateTime<Utc>,
+) -> ListItem<'a> {
+    let name = display_name(repo, account);
+    let (marker, color) = match repo.status {
+        // Protected repositories and those without admin rights can't be marked, so they are
+        // always unselected
+        _ if locked => ("⊘", DARK_GRAY),
+        Status::Unselected => ("☐", TEXT_FG_COLOR),
+        Status::Marked(Action::Delete) => ("✓", COMPLETED_TEXT_FG_COLOR),
+        Status::Marked(Action::Archive) => ("⊟", ARCHIVE_FG_COLOR),
+        Status::Marked(Action::Unarchive) => ("⊞", ARCHIVE_FG_COLOR),
+        Status::Marked(Action::MakePrivate) => ("◌", PRIVATE_FG_COLOR),
+    };
+
+    // Highlights are relative to the repository name, which may be prefixed with its owner
+    let offset = name.chars().count() - repo.name.chars().count();
+    let mut spans = vec![Span::styled(format!(" {marker} "), color)];
+    spans.extend(name.chars().enumerate().map(|(i, c)| {
+        let style = if i >= offset && highlights.contains(&(i - offset)) {
+            Style::default()
+                .fg(SEARCH_MATCH_FG_COLOR)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(color)
+        };
+        Span::styled(c.to_string(), style)
+    }));
+    let padding = name_width.saturating_sub(name.chars().count()) + 2;
+    spans.push(Span::raw(" ".repeat(padding)));
+    spans.push(Span::styled(
+        metadata_columns(&repo.metadata, now),
+        DARK_GRAY,
+    ));
+    if let Some(rule) = &repo.matched_rule {
+        spans.push(Span::styled(format!(" · rule: {rule}"), RULE_FG_COLOR));
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+// What went wrong, what to do about it and where the forge documents it
+pub fn render_error(frame: &mut Frame, error: &Error, forge: ForgeKind) {
+    let mut text = vec![
+        Line::from(error.message()),
+        Line::from(""),
+        Line::from(error.hint(forge)).fg(DARK_GRAY),
+    ];
+    if let Some(url) = error.documentation_url() {
+        text.push(Line::from(""));
+        text.push(Line::from(url.to_owned()).fg(DARK_GRAY));
+    }
+    text.push(Line::from(""));
+    text.push(Line::from("Press 'Enter' or 'Esc' to close").fg(DARK_GRAY));
+
+    let block = Block::bordered()
+        .title(Line::raw(format!(" {} ", error.title())).centered())
+        .border_style(Style::default().fg(LIGHT_RED));
+    let area = popup_area(frame.area(), 60, 40);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+pub fn render_rulesets(frame: &mut Frame, rulesets: &[Ruleset], list_state: &mut ListState) {
+    let block = Block::new()
+        .title(Line::raw("Apply a ruleset").centered())
+        .borders(Borders::ALL)
+        .style(Style::default().fg(LIGHT_RED));
+
+    let items: Vec<ListItem> = rulesets
+        .iter()
+        .map(|ruleset| {
+            let rules: Vec<&str> = ruleset
+                .rules
+                .iter()
+                .map(|rule| rule.name.as_str())
+                .collect();
+            ListItem::new(Line::from(vec![
+                Span::styled(ruleset.name.clone(), TEXT_FG_COLOR),
+                Span::styled(format!("  {}", rules.join(", ")), DARK_GRAY),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(SELECTED_STYLE)
+        .highlight_symbol(">")
+        .highlight_spacing(HighlightSpacing::Always);
+
+    let area = popup_area(frame.area(), 60, 40);
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, list_state);
+}
+
+// The accounts whose repositories can be listed, the user's own first
+pub fn render_owners(owners: &[String], list_state: &mut ListState, area: Rect, buf: &mut Buffer) {
+    let block = Block::new()
+        .title(Line::raw("Whose repositories do you want to clean up?").centered())
+        .borders(Borders::TOP)
+        .style(Style::default().fg(LIGHT_RED));
+
+    let items: Vec<ListItem> = owners
+        .iter()
+        .enumerate()
+        .map(|(i, owner)| {
+            let kind = if i == 0 {
+                "  your account"
+            } else {
+                "  organization"
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(owner.clone(), TEXT_FG_COLOR),
+                Span::styled(kind, DARK_GRAY),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(SELECTED_STYLE)
+        .highlight_symbol(">")
+        .highlight_spacing(HighlightSpacing::Always);
+
+    StatefulWidget::render(list, area, buf, list_state);
+}
+
+// Asks for the answer right below the marked repositories
+pub fn draw_confirmation_input(frame: &mut Frame, prompt: &str, input: &str, character_index: u16) {
+    let popup = popup_area(frame.area(), 80, 40);
+    let area = Rect {
+        y: popup.bottom(),
+        height: 3,
+        ..popup
+    }
+    .intersection(frame.area());
+
+    let block = Block::bordered()
+        .title(format!(" {prompt} "))
+        .border_style(Style::default().fg(LIGHT_RED));
+    frame.render_widget(Paragraph::new(input).block(block), area);
+    frame.set_cursor_position(Position::new(area.x + character_index + 1, area.y + 1));
+}
+
+pub fn draw_search_input(frame: &mut Frame, area: Rect, search: &str, character_index: u16) {
+    let line = Line::from(vec![
+        Span::styled("/", Style::default().fg(LIGHT_RED)),
+        Span::raw(search.to_owned()),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+    frame.set_cursor_position(Position::new(area.x + character_index + 1, area.y));
+}
+
+// Renders the metadata as fixed width columns, e.g. "last push 3y ago · ★2 · 120 KB · Rust · fork"
+fn metadata_columns(metadata: &RepositoryMetadata, now: DateTime<Utc>) -> String {
+    let pushed = match metadata.pushed_at {
+        Some(pushed_at) => format!("last push {}", relative_time(pushed_at, now)),
+        None => String::from("never pushed"),
+    };
+    let stars = format!("★{}", metadata.stargazers_count);
+    let size = format_size(metadata.size);
+    let language = metadata.language.as_deref().unwrap_or("-");
+
+    let mut columns = format!("{pushed:<19} · {stars:<5} · {size:>8} · {language:<10}");
+    for (flag, label) in [
+        (metadata.private, "private"),
+        (metadata.fork, "fork"),
+        (metadata.archived, "archived"),
+    ] {
+        if flag {
+            columns.push_str(" · ");
+            columns.push_str(label);
+        }
+    }
+    columns
 }
 
-impl From<&Repository> for ListItem<'_> {
-    fn from(value: &Repository) -> Self {
-        let line = match value.status {
-            Status::Unselected => Line::styled(format!(" ☐ {}", value.name), TEXT_FG_COLOR),
-            Status::Selected => Line::styled(format!(" ✓ {}", value.name), COMPLETED_TEXT_FG_COLOR),
+pub fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(time);
+    let days = elapsed.num_days();
+    if days >= 365 {
+        format!("{}y ago", days / 365)
+    } else if days >= 30 {
+        format!("{}mo ago", days / 30)
+    } else if days >= 1 {
+        format!("{days}d ago")
+    } else if elapsed.num_hours() >= 1 {
+        format!("{}h ago", elapsed.num_hours())
+    } else {
+        String::from("just now")
+    }
+}
+
+// Rounded up, "1 min" also covers the last few seconds
+fn minutes_until(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = time.signed_duration_since(now).num_seconds().max(0);
+    format!("{} min", (seconds + 59) / 60)
+}
+
+// GitHub reports repository sizes in kilobytes
+pub fn format_size(kilobytes: u64) -> String {
+    const MB: u64 = 1024;
+    const GB: u64 = 1024 * MB;
+    if kilobytes >= GB {
+        format!("{:.1} GB", kilobytes as f64 / GB as f64)
+    } else if kilobytes >= MB {
+        format!("{:.1} MB", kilobytes as f64 / MB as f64)
+    } else {
+        format!("{kilobytes} KB")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_relative_time_uses_largest_unit() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            relative_time(now - Duration::days(3 * 365 + 2), now),
+            "3y ago"
+        );
+        assert_eq!(relative_time(now - Duration::days(65), now), "2mo ago");
+        assert_eq!(relative_time(now - Duration::days(4), now), "4d ago");
+        assert_eq!(relative_time(now - Duration::hours(5), now), "5h ago");
+        assert_eq!(relative_time(now - Duration::minutes(5), now), "just now");
+    }
+
+    fn repository(name: &str, pushed_at: Option<DateTime<Utc>>, size: u64) -> Repository {
+        Repository {
+            name: name.to_owned(),
+            owner: String::from("octocat"),
+            status: Status::Unselected,
+            metadata: RepositoryMetadata {
+                pushed_at,
+                size,
+                ..RepositoryMetadata::default()
+            },
+            matched_rule: None,
+        }
+    }
+
+    fn visible_names(listing: &RepositoryListing) -> Vec<&str> {
+        listing
+            .visible
+            .iter()
+            .map(|visible| listing.repos[visible.index].name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sorting_keeps_cursor_on_repository() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let repos = vec![
+            repository("beta", Some(now - Duration::days(10)), 50),
+            repository("Alpha", Some(now), 10),
+            repository("gamma", None, 30),
+        ];
+        let mut listing = RepositoryListing::new("octocat", repos, None, RepoQuery::default());
+        assert_eq!(visible_names(&listing), vec!["Alpha", "beta", "gamma"]);
+        listing.list_state.select(Some(1));
+
+        listing.cycle_sort_key();
+        assert_eq!(listing.sort_key, SortKey::PushedAt);
+        assert_eq!(visible_names(&listing), vec!["gamma", "beta", "Alpha"]);
+        assert_eq!(listing.highlighted().unwrap().name, "beta");
+
+        listing.toggle_sort_direction();
+        listing.cycle_sort_key();
+        listing.cycle_sort_key();
+        listing.cycle_sort_key();
+        assert_eq!(listing.sort_key, SortKey::Size);
+        assert_eq!(visible_names(&listing), vec!["beta", "gamma", "Alpha"]);
+        assert_eq!(listing.list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(120), "120 KB");
+        assert_eq!(format_size(1536), "1.5 MB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_metadata_columns() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let metadata = RepositoryMetadata {
+            pushed_at: Some(now - Duration::days(3 * 365 + 10)),
+            stargazers_count: 2,
+            size: 120,
+            fork: true,
+            language: Some(String::from("Rust")),
+            ..RepositoryMetadata::default()
         };
-        ListItem::new(line)
+        assert_eq!(
+            metadata_columns(&metadata, now),
+            "last push 3y ago    · ★2    ·   120 KB · Rust       · fork"
+        );
     }
 }